@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+
+use rand::prelude::*;
+
+use crate::{Bullet, Evolution, Physics, ProjectileKind, Tank, Turret};
+
+/// Bosses are their own team, see `Tank::team`
+pub const BOSS_TEAM: u8 = 255;
+/// xp shared by the tanks that damaged a boss, by how much damage they dealt
+pub const BOSS_XP: f64 = 20_000.;
+/// bosses only see tanks this close
+const RANGE: f64 = 3072.;
+/// seconds between the ring attacks of an enraged boss
+const NOVA_RELOAD: f64 = 4.;
+
+/// A boss tank: a big hp pool, 8 cannons around it and 2 drone spawners on the sides. Textured by svg/boss.svg
+pub fn boss_tank() -> Tank {
+    let mut turrets: Vec<Turret> = (0..8).map(|i| Turret {
+        projectile_impulse: 4_000.,
+        projectile_weight: 6.,
+        projectile_collision_size: 24.,
+        projectile_hp_regen: -2.,
+        projectile_hp: 6.,
+        reload_time: 1.5,
+        inaccuracy: 2.,
+        relative_direction: i as f64 * 45.,
+        relative_position: (0., -260.),
+        ..Default::default()
+    }).collect();
+    for direction in [90., 270.] {
+        turrets.push(Turret {
+            projectile_impulse: 3_000.,
+            projectile_weight: 8.,
+            projectile_collision_size: 20.,
+            projectile_hp_regen: -1.,
+            projectile_hp: 16.,
+            reload_time: 3.,
+            inaccuracy: 1.,
+            relative_direction: direction,
+            relative_position: (0., -200.),
+            projectile_texture: "drone".to_owned(),
+            projectile_kind: ProjectileKind::Drone,
+            ..Default::default()
+        });
+    }
+
+    Tank {
+        physics: Physics {
+            x: 0.,
+            y: 0.,
+            xvel: 0.,
+            yvel: 0.,
+            weight: 2000.,
+            rot: 0.,
+            rotvel: 0.,
+            collision_size: 150.,
+            hp: 3000.,
+            max_hp: 3000.,
+            hp_regen: 2.,
+        },
+        turrets,
+        power: 100_000.,
+        rot_power: 2000.,
+        texture: "boss".to_owned(),
+        evolution: Evolution {
+            class: "boss".to_owned(),
+            // killing a boss gives no xp directly, `BOSS_XP` is split by damage instead
+            killvalue: 0.,
+            ..Evolution::new()
+        },
+        team: BOSS_TEAM,
+        ..Default::default()
+    }
+}
+
+/// Controls one boss. Unlike `TankAI` it never upgrades or flees, and its behaviour depends on the hp left:
+///
+/// 0. above 2/3 hp it guards its position, spinning and firing all around
+/// 1. above 1/3 hp it hunts the closest tank
+/// 2. below that it is enraged, hunts while spinning, reloads twice as fast and fires rings of bullets
+pub struct BossAI {
+    pub id: u128,
+    phase: usize,
+    target: u128,
+    /// seconds until the next ring attack
+    nova_cooldown: f64,
+}
+impl BossAI {
+    pub fn new(id: u128) -> Self {
+        BossAI {
+            id,
+            phase: 0,
+            target: 0,
+            nova_cooldown: NOVA_RELOAD,
+        }
+    }
+
+    /// Moves and fires the boss, announces phase changes. Returns false if the boss is dead
+    pub fn control(&mut self, tanks: &mut HashMap<u128, Tank>, bullets: &mut HashMap<u128, Bullet>, announcements: &mut Vec<String>, delta: f64) -> bool {
+        let Some(boss) = tanks.get(&self.id) else {
+            return false
+        };
+        let bp = boss.physics;
+
+        // phases only go forward, healing does not calm the boss down
+        let phase = if bp.hp > bp.max_hp*2./3. {0} else if bp.hp > bp.max_hp/3. {1} else {2};
+        if phase > self.phase {
+            self.phase = phase;
+            if phase == 1 {
+                announcements.push("The boss is hunting".to_owned());
+            } else {
+                announcements.push("The boss is enraged".to_owned());
+                for turret in tanks.get_mut(&self.id).unwrap().turrets.iter_mut() {
+                    turret.reload_time *= 0.5;
+                }
+            }
+        }
+
+        // keep the target while it is in range, otherwise take the closest tank
+        if !tanks.get(&self.target).is_some_and(|t| t.physics.dist(&bp) < RANGE && !t.hidden_at(&bp)) {
+            self.target = tanks.iter()
+                .filter(|(id, t)| **id != self.id && t.team != BOSS_TEAM && t.physics.dist(&bp) < RANGE && !t.hidden_at(&bp))
+                .min_by(|a, b| a.1.physics.dist(&bp).partial_cmp(&b.1.physics.dist(&bp)).unwrap())
+                .map_or(0, |(id, _)| *id);
+        }
+        let Some(target) = tanks.get(&self.target).map(|t| (t.physics.x, t.physics.y)) else {
+            // nobody around, the boss rests
+            return true
+        };
+
+        let boss = tanks.get_mut(&self.id).unwrap();
+        let to_target = (target.0 - bp.x, target.1 - bp.y);
+        match self.phase {
+            0 => {
+                boss.physics.rotvel = 20.;
+                boss.firing_to = target;
+            }
+            1 => {
+                boss.rotate_to(target, delta);
+                boss.move_in_dir(to_target, delta);
+            }
+            _ => {
+                boss.physics.rotvel = 60.;
+                boss.firing_to = target;
+                boss.move_in_dir(to_target, delta);
+
+                self.nova_cooldown -= delta;
+                if self.nova_cooldown <= 0. {
+                    self.nova_cooldown = NOVA_RELOAD;
+                    nova(boss, self.id, bullets);
+                }
+            }
+        }
+        boss.fire(bullets, self.id);
+        true
+    }
+}
+
+/// Fires a ring of 32 bullets around the boss
+fn nova(boss: &mut Tank, boss_id: u128, bullets: &mut HashMap<u128, Bullet>) {
+    for i in 0..32 {
+        let angle = (i as f64 / 32. * 360.).to_radians();
+        let mut physics = boss.physics;
+        physics.weight = 8.;
+        physics.collision_size = 30.;
+        physics.x += angle.cos() * boss.physics.collision_size;
+        physics.y += angle.sin() * boss.physics.collision_size;
+        physics.push((angle.cos() * 5_000., angle.sin() * 5_000.));
+        physics.hp = 8.;
+        physics.max_hp = 8.;
+        physics.hp_regen = -2.;
+
+        let id = thread_rng().gen::<u128>();
+        boss.bullet_ids.insert(id);
+        bullets.insert(id, Bullet {
+            physics,
+            source_tank_id: boss_id,
+            team: boss.team,
+            texture: "bullet".to_owned(),
+            kind: ProjectileKind::Bullet,
+            rocket: None,
+        });
+    }
+}
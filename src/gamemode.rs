@@ -0,0 +1,1395 @@
+use std::collections::{HashMap, HashSet};
+
+use rand::prelude::*;
+use sdl2::gfx::primitives::DrawRenderer;
+use sdl2::pixels::Color;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::walls::Wall;
+use crate::tank_tree::{EVOLUTION_TREE, ROOT_CLASS};
+use crate::{draw_circle_outline, normalize, team_color, Camera, Evolution, Map, Shape, Temper};
+
+/// Names of all the gamemodes, as accepted by `from_name`
+pub const GAMEMODES: [&str; 10] = ["ffa", "survival", "teams2", "teams4", "domination", "maze", "sandbox", "waves", "infection", "ctf"];
+
+/// Classes the gamemodes spawn by name, besides `ROOT_CLASS`. See `tank_tree::validate`
+pub fn required_classes() -> Vec<&'static str> {
+    let mut classes = Domination::DEFENDERS.to_vec();
+    classes.push(Infection::INFECTED_CLASS);
+    classes
+}
+
+/// Creates a gamemode by its name, None if there is no such gamemode
+pub fn from_name(name: &str) -> Option<Box<dyn Gamemode>> {
+    match name {
+        "ffa" => Some(Box::new(Ffa)),
+        "survival" => Some(Box::new(Survival::default())),
+        "teams2" => Some(Box::new(Teams::new(2))),
+        "teams4" => Some(Box::new(Teams::new(4))),
+        "domination" => Some(Box::new(Domination::new(2))),
+        "maze" => Some(Box::new(Maze::default())),
+        "sandbox" => Some(Box::new(Sandbox::default())),
+        "waves" => Some(Box::new(Waves::default())),
+        "infection" => Some(Box::new(Infection::default())),
+        "ctf" => Some(Box::new(Ctf::new())),
+        _ => None,
+    }
+}
+
+/// The rules of a game. Every room has its own gamemode object, so the gamemode can keep state like scores or rounds.
+///
+/// The hooks are called by `Room`. Every hook has a default that behaves like FFA, so a gamemode only implements what it changes.
+pub trait Gamemode: Send {
+    /// Shown to players and used to select the gamemode, see `from_name`
+    fn name(&self) -> &str;
+
+    /// Called once when the room is created, before anything spawns. Can build the map, like walls
+    fn init(&mut self, _map: &mut Map) {}
+
+    /// The team of a tank, 0 for no team. Called before `spawn_position`, every time the tank spawns
+    fn team_for(&mut self, _map: &Map, _id: u128) -> u8 {
+        0
+    }
+
+    /// Where a tank appears. Called for players, network players and bots, after the tank is added to the map and got its team
+    fn spawn_position(&mut self, map: &Map, _id: u128) -> (f64, f64) {
+        (
+            thread_rng().gen::<f64>()*map.map_size.0*2. - map.map_size.0,
+            thread_rng().gen::<f64>()*map.map_size.1*2. - map.map_size.1,
+        )
+    }
+
+    /// How many tanks (players included) the map is filled to with bots this frame. `bots` is the number in the room settings
+    fn bot_count(&mut self, _map: &Map, bots: usize) -> usize {
+        bots
+    }
+
+    /// Called every frame, after physics
+    fn on_tick(&mut self, _map: &mut Map, _delta: f64) {}
+
+    /// Called when the tank `victim` was killed by the tank `killer`, before `on_tank_death`. The victim is already removed from the map
+    fn on_kill(&mut self, _map: &mut Map, _killer: u128, _victim: u128) {}
+
+    /// Called for every tank that died, killed by another tank or not. The tank is already removed from the map
+    fn on_tank_death(&mut self, _map: &mut Map, _id: u128) {}
+
+    /// If the object `a` deals damage to the object `b` when they collide. Objects are tanks, shapes and bullets.
+    /// Collisions still push the objects when this is false
+    fn can_damage(&self, _map: &Map, _a: u128, _b: u128) -> bool {
+        true
+    }
+
+    /// If the player `id` gets a new tank now. Called every frame while the player has no tank
+    fn can_respawn(&self, _map: &Map, _id: u128) -> bool {
+        true
+    }
+
+    /// Some(description of the winner) once the game is won
+    fn winner(&self, _map: &Map) -> Option<String> {
+        None
+    }
+
+    /// One line about the state of the game shown to players, like a countdown. Empty if there is nothing to show
+    fn status(&self, _map: &Map) -> String {
+        String::new()
+    }
+
+    /// Draws things of the gamemode on the map, like control points. Called after the map is rendered, before the objects on it
+    fn render(&self, _canvas: &mut Canvas<Window>, _camera: &Camera) {}
+
+    /// While true the room is not updated and players can not move or fire, like a pause
+    fn paused(&self) -> bool {
+        false
+    }
+
+    /// The sandbox tools, None in every gamemode except the sandbox
+    fn sandbox(&mut self) -> Option<&mut Sandbox> {
+        None
+    }
+}
+
+/// Free for all, the map is always full of bots, everyone respawns
+pub struct Ffa;
+impl Gamemode for Ffa {
+    fn name(&self) -> &str {
+        "ffa"
+    }
+}
+
+enum SurvivalPhase {
+    /// seconds until the round starts. Tanks respawn and deal no damage
+    Lobby(f64),
+    /// bots are spawned this frame, the round starts after it
+    Starting,
+    Running,
+    /// seconds until the next lobby
+    Ended(f64),
+}
+
+/// Last tank standing, in rounds. After a lobby countdown bots are spawned, nobody respawns, and the map gets smaller until one tank or team is left.
+/// The winner is announced, and the next round starts in a new lobby
+pub struct Survival {
+    phase: SurvivalPhase,
+    /// tanks still in the round
+    alive: HashSet<u128>,
+    /// tanks that are out of the round, the first eliminated first
+    eliminated: Vec<u128>,
+    /// size of the map at the start of the round, it is restored for the next round
+    map_size: Option<(f64, f64)>,
+    winner: Option<String>,
+}
+impl Survival {
+    const LOBBY_TIME: f64 = 10.;
+    const RESULTS_TIME: f64 = 8.;
+
+    /// Some(description of the winner) if the round is over
+    fn check_winner(&self, map: &Map) -> Option<String> {
+        let mut teams = self.alive.iter().map(|id| map.team_of(id));
+        let first_team = teams.next();
+        if self.alive.len() <= 1 {
+            match self.alive.iter().next() {
+                Some(id) => Some(format!("tank {} ({})", id, map.tanks.get(id).unwrap().evolution.class)),
+                None => Some("nobody".to_owned()),
+            }
+        } else if first_team != Some(0) && teams.all(|t| Some(t) == first_team) {
+            Some(format!("team {}", first_team.unwrap()))
+        } else {
+            None
+        }
+    }
+}
+impl Default for Survival {
+    fn default() -> Self {
+        Survival {
+            phase: SurvivalPhase::Lobby(Self::LOBBY_TIME),
+            alive: HashSet::new(),
+            eliminated: vec![],
+            map_size: None,
+            winner: None,
+        }
+    }
+}
+impl Gamemode for Survival {
+    fn name(&self) -> &str {
+        "survival"
+    }
+
+    fn bot_count(&mut self, _map: &Map, bots: usize) -> usize {
+        match self.phase {
+            SurvivalPhase::Starting => bots,
+            _ => 0,
+        }
+    }
+
+    fn on_tick(&mut self, map: &mut Map, delta: f64) {
+        match self.phase {
+            SurvivalPhase::Lobby(time) => {
+                // the zone waits for the round
+                if let Some(zone) = &mut map.zone {
+                    zone.restart();
+                }
+                self.phase = if time - delta <= 0. {SurvivalPhase::Starting} else {SurvivalPhase::Lobby(time - delta)};
+            }
+            SurvivalPhase::Starting => {
+                self.alive = map.tanks.keys().copied().collect();
+                self.eliminated.clear();
+                self.map_size = Some(map.map_size);
+                // a round needs at least 2 tanks, wait for more players otherwise
+                self.phase = if self.alive.len() < 2 {SurvivalPhase::Lobby(Self::LOBBY_TIME)} else {SurvivalPhase::Running};
+            }
+            SurvivalPhase::Running => {
+                // players that left the room are out too
+                for id in self.alive.iter().filter(|id| !map.tanks.contains_key(id)).copied().collect::<Vec<u128>>() {
+                    self.alive.remove(&id);
+                    self.eliminated.push(id);
+                }
+
+                // Survival map gets smaller, unless the zone does that
+                if map.zone.is_none() {
+                    map.map_size.0 -= (delta * 16.).min(map.map_size.0*delta/128.);
+                    map.map_size.1 -= (delta * 16.).min(map.map_size.1*delta/128.);
+                }
+
+                self.winner = self.check_winner(map);
+                if let Some(winner) = &self.winner {
+                    let places: Vec<String> = self.eliminated.iter().rev().enumerate()
+                        .map(|(i, id)| format!("{}. tank {}", i + self.alive.len() + 1, id))
+                        .collect();
+                    println!("Survival round over, {} won. {}", winner, places.join(", "));
+                    self.phase = SurvivalPhase::Ended(Self::RESULTS_TIME);
+                }
+            }
+            SurvivalPhase::Ended(time) => {
+                if time - delta > 0. {
+                    self.phase = SurvivalPhase::Ended(time - delta);
+                    return
+                }
+                // reset into the next round, everyone gets a new tank in the lobby
+                map.tanks.clear();
+                map.tankais.clear();
+                map.bullets.clear();
+                if let Some(size) = self.map_size {
+                    map.map_size = size;
+                }
+                self.alive.clear();
+                self.winner = None;
+                self.phase = SurvivalPhase::Lobby(Self::LOBBY_TIME);
+            }
+        }
+    }
+
+    fn on_tank_death(&mut self, _map: &mut Map, id: u128) {
+        if self.alive.remove(&id) {
+            self.eliminated.push(id);
+        }
+    }
+
+    fn can_damage(&self, _map: &Map, _a: u128, _b: u128) -> bool {
+        !matches!(self.phase, SurvivalPhase::Lobby(_))
+    }
+
+    fn can_respawn(&self, _map: &Map, _id: u128) -> bool {
+        matches!(self.phase, SurvivalPhase::Lobby(_) | SurvivalPhase::Starting)
+    }
+
+    fn winner(&self, _map: &Map) -> Option<String> {
+        self.winner.clone()
+    }
+
+    fn status(&self, _map: &Map) -> String {
+        match self.phase {
+            SurvivalPhase::Lobby(time) => format!("Round starts in {:.0}", time.ceil()),
+            SurvivalPhase::Starting => "Round starts".to_owned(),
+            SurvivalPhase::Running => format!("{} tanks left", self.alive.len()),
+            SurvivalPhase::Ended(time) => format!("{} won, next round in {:.0}", self.winner.as_deref().unwrap_or("nobody"), time.ceil()),
+        }
+    }
+}
+
+/// Team deathmatch with 2 or 4 teams. Teammates do not damage each other, every team spawns in its own base, and the first team to reach `Teams::KILLS_TO_WIN` kills wins
+pub struct Teams {
+    teams: u8,
+    /// team of every tank that spawned. Players keep their team when they respawn, bots are forgotten when they die
+    assigned: HashMap<u128, u8>,
+    /// kills of every team, index 0 is team 1
+    kills: Vec<u32>,
+}
+impl Teams {
+    const KILLS_TO_WIN: u32 = 100;
+
+    pub fn new(teams: u8) -> Self {
+        Teams {
+            teams,
+            assigned: HashMap::new(),
+            kills: vec![0; teams as usize],
+        }
+    }
+}
+impl Gamemode for Teams {
+    fn name(&self) -> &str {
+        if self.teams == 2 {"teams2"} else {"teams4"}
+    }
+
+    fn team_for(&mut self, map: &Map, id: u128) -> u8 {
+        if let Some(team) = self.assigned.get(&id) {
+            return *team
+        }
+        // join the team with the fewest tanks
+        let team = (1..=self.teams)
+            .min_by_key(|team| map.tanks.values().filter(|t| t.team == *team).count())
+            .unwrap();
+        self.assigned.insert(id, team);
+        team
+    }
+
+    fn spawn_position(&mut self, map: &Map, id: u128) -> (f64, f64) {
+        let (w, h) = map.map_size;
+        let team = map.tanks.get(&id).map_or(0, |t| t.team);
+        // bases are strips at the left and right edge for 2 teams, and squares in the corners for 4 teams
+        let (xmin, xmax, ymin, ymax) = match (self.teams, team) {
+            (2, 1) => (-w, -w*0.8, -h, h),
+            (2, 2) => (w*0.8, w, -h, h),
+            (4, 1) => (-w, -w*0.8, -h, -h*0.8),
+            (4, 2) => (w*0.8, w, h*0.8, h),
+            (4, 3) => (w*0.8, w, -h, -h*0.8),
+            (4, 4) => (-w, -w*0.8, h*0.8, h),
+            _ => (-w, w, -h, h),
+        };
+        (
+            xmin + thread_rng().gen::<f64>()*(xmax - xmin),
+            ymin + thread_rng().gen::<f64>()*(ymax - ymin),
+        )
+    }
+
+    fn on_kill(&mut self, map: &mut Map, killer: u128, _victim: u128) {
+        let team = map.team_of(&killer);
+        if team != 0 {
+            self.kills[team as usize - 1] += 1;
+        }
+    }
+
+    fn on_tank_death(&mut self, map: &mut Map, id: u128) {
+        // the AI of a dead bot is only removed on the next frame
+        if map.tankais.iter().any(|ai| ai.id == id) {
+            self.assigned.remove(&id);
+        }
+    }
+
+    fn can_damage(&self, map: &Map, a: u128, b: u128) -> bool {
+        let team = map.team_of(&a);
+        team == 0 || team != map.team_of(&b)
+    }
+
+    fn winner(&self, _map: &Map) -> Option<String> {
+        self.kills.iter().position(|k| *k >= Self::KILLS_TO_WIN).map(|i| format!("team {}", i + 1))
+    }
+}
+
+/// A circle on the map that a team captures by having more tanks inside of it than any other team
+struct ControlPoint {
+    position: (f64, f64),
+    radius: f64,
+    /// 0 if no team owns it
+    owner: u8,
+    /// the team taking over the point, and how far it is, from 0 to 1
+    capture: (u8, f64),
+    /// ids of the turrets spawned for the owner
+    defenders: Vec<u128>,
+}
+
+/// Teams capture control points. Owned points get defensive turrets and give their team score every second.
+/// A team wins by holding all the points or reaching `Domination::SCORE_TO_WIN`
+pub struct Domination {
+    /// team assignment, bases and friendly fire work like in team deathmatch
+    teams: Teams,
+    /// placed on the first frame, when the map size is known
+    points: Vec<ControlPoint>,
+    /// index 0 is team 1
+    score: Vec<f64>,
+}
+impl Domination {
+    const CAPTURE_TIME: f64 = 10.;
+    const SCORE_TO_WIN: f64 = 300.;
+    /// classes of the turrets spawned on a captured point
+    const DEFENDERS: [&'static str; 2] = ["triple", "spawner"];
+
+    pub fn new(teams: u8) -> Self {
+        Domination {
+            teams: Teams::new(teams),
+            points: vec![],
+            score: vec![0.; teams as usize],
+        }
+    }
+
+    /// the team with more tanks inside the point than any other team, None if there is a tie or nobody is inside
+    fn leader(map: &Map, point: &ControlPoint) -> Option<u8> {
+        let mut counts: HashMap<u8, usize> = HashMap::new();
+        for tank in map.tanks.values().filter(|t| t.team != 0) {
+            if (tank.physics.x - point.position.0).powi(2) + (tank.physics.y - point.position.1).powi(2) < point.radius.powi(2) {
+                *counts.entry(tank.team).or_default() += 1;
+            }
+        }
+        let most = *counts.values().max()?;
+        let mut leaders = counts.iter().filter(|(_, c)| **c == most);
+        let leader = *leaders.next()?.0;
+        if leaders.next().is_some() {None} else {Some(leader)}
+    }
+}
+impl Gamemode for Domination {
+    fn name(&self) -> &str {
+        "domination"
+    }
+
+    fn team_for(&mut self, map: &Map, id: u128) -> u8 {
+        self.teams.team_for(map, id)
+    }
+
+    fn spawn_position(&mut self, map: &Map, id: u128) -> (f64, f64) {
+        self.teams.spawn_position(map, id)
+    }
+
+    fn bot_count(&mut self, _map: &Map, bots: usize) -> usize {
+        // defenders do not take the place of bots
+        bots + self.points.iter().map(|p| p.defenders.len()).sum::<usize>()
+    }
+
+    fn on_tick(&mut self, map: &mut Map, delta: f64) {
+        if self.points.is_empty() {
+            let (w, h) = map.map_size;
+            let mut positions = vec![(-w/2., 0.), (0., 0.), (w/2., 0.)];
+            if self.teams.teams == 4 {
+                positions.extend([(0., -h/2.), (0., h/2.)]);
+            }
+            self.points = positions.into_iter().map(|position| ControlPoint {
+                position,
+                radius: 400.,
+                owner: 0,
+                capture: (0, 0.),
+                defenders: vec![],
+            }).collect();
+        }
+
+        for point in self.points.iter_mut() {
+            point.defenders.retain(|id| map.tanks.contains_key(id));
+
+            match Self::leader(map, point) {
+                Some(leader) if leader != point.owner => {
+                    if point.capture.0 == leader {
+                        point.capture.1 += delta / Self::CAPTURE_TIME;
+                    } else {
+                        // another team has to undo the progress first
+                        point.capture.1 -= delta / Self::CAPTURE_TIME;
+                        if point.capture.1 <= 0. {
+                            point.capture = (leader, 0.);
+                        }
+                    }
+                }
+                // the progress goes back while the owner holds the point or nobody is there
+                _ => point.capture.1 = (point.capture.1 - delta / Self::CAPTURE_TIME).max(0.),
+            }
+
+            if point.capture.1 >= 1. {
+                point.owner = point.capture.0;
+                point.capture = (0, 0.);
+                for id in point.defenders.drain(..) {
+                    map.tanks.remove(&id);
+                }
+                for (i, class) in Self::DEFENDERS.iter().enumerate() {
+                    let id = map.spawn_bot(class);
+                    map.tankais.last_mut().unwrap().stationary = true;
+                    let tank = map.tanks.get_mut(&id).unwrap();
+                    tank.team = point.owner;
+                    let angle = i as f64 / Self::DEFENDERS.len() as f64 * std::f64::consts::TAU;
+                    tank.physics.x = point.position.0 + angle.cos() * point.radius / 2.;
+                    tank.physics.y = point.position.1 + angle.sin() * point.radius / 2.;
+                    point.defenders.push(id);
+                }
+            }
+
+            if point.owner != 0 {
+                self.score[point.owner as usize - 1] += delta;
+            }
+        }
+
+        // bots go to the closest point their team does not own
+        for ai in map.tankais.iter_mut().filter(|ai| !ai.stationary) {
+            let Some(tank) = map.tanks.get(&ai.id) else {
+                continue
+            };
+            let dist = |p: &&ControlPoint| (p.position.0 - tank.physics.x).powi(2) + (p.position.1 - tank.physics.y).powi(2);
+            ai.objective = self.points.iter()
+                .filter(|p| p.owner != tank.team)
+                .min_by(|a, b| dist(a).partial_cmp(&dist(b)).unwrap())
+                .map(|p| p.position);
+        }
+    }
+
+    fn on_kill(&mut self, map: &mut Map, killer: u128, victim: u128) {
+        self.teams.on_kill(map, killer, victim);
+    }
+
+    fn on_tank_death(&mut self, map: &mut Map, id: u128) {
+        self.teams.on_tank_death(map, id);
+    }
+
+    fn can_damage(&self, map: &Map, a: u128, b: u128) -> bool {
+        self.teams.can_damage(map, a, b)
+    }
+
+    fn winner(&self, _map: &Map) -> Option<String> {
+        let first_owner = self.points.first()?.owner;
+        if first_owner != 0 && self.points.iter().all(|p| p.owner == first_owner) {
+            return Some(format!("team {}", first_owner))
+        }
+        self.score.iter().position(|s| *s >= Self::SCORE_TO_WIN).map(|i| format!("team {}", i + 1))
+    }
+
+    fn status(&self, _map: &Map) -> String {
+        let scores: Vec<String> = self.score.iter().enumerate().map(|(i, s)| format!("team {}: {:.0}", i + 1, s)).collect();
+        let points: Vec<String> = self.points.iter().enumerate().map(|(i, p)| {
+            let name = (b'A' + i as u8) as char;
+            if p.capture.1 > 0. {
+                format!("{}: team {} {:.0}%", name, p.capture.0, p.capture.1 * 100.)
+            } else if p.owner != 0 {
+                format!("{}: team {}", name, p.owner)
+            } else {
+                format!("{}: free", name)
+            }
+        }).collect();
+        format!("{} | {}", scores.join(", "), points.join(", "))
+    }
+
+    fn render(&self, canvas: &mut Canvas<Window>, camera: &Camera) {
+        let zoom = camera.zoom * ((camera.viewport_size.0.pow(2) + camera.viewport_size.1.pow(2)) as f64).sqrt() / 1024.;
+        for point in &self.points {
+            let center = camera.to_screen_coords(point.position);
+            draw_circle_outline(canvas, center, point.radius * zoom, if point.owner == 0 {Color::GRAY} else {team_color(point.owner)});
+            // the capture progress fills the point from the center
+            if point.capture.1 > 0. {
+                let radius = (point.radius * zoom * point.capture.1).min(i16::MAX as f64) as i16;
+                canvas.filled_circle(center.0 as i16, center.1 as i16, radius, team_color(point.capture.0)).unwrap();
+            }
+        }
+    }
+}
+
+/// Free for all in a maze of walls. The maze is generated from the map seed, so the same seed gives the same maze
+#[derive(Default)]
+pub struct Maze {
+    /// number of cells in x and y
+    cells: (usize, usize),
+    /// size of one cell in map units
+    cell_size: (f64, f64),
+}
+impl Maze {
+    /// about how big a cell is, the map is split into whole cells
+    const CELL_SIZE: f64 = 1000.;
+    const WALL_THICKNESS: f64 = 80.;
+    /// part of the walls removed after the maze is generated, so there is more than one way around
+    const OPENNESS: f64 = 0.15;
+}
+impl Gamemode for Maze {
+    fn name(&self) -> &str {
+        "maze"
+    }
+
+    fn init(&mut self, map: &mut Map) {
+        let mut rng = StdRng::seed_from_u64(map.seed);
+        let (w, h) = map.map_size;
+        let (cols, rows) = (((w*2.) / Self::CELL_SIZE).max(1.) as usize, ((h*2.) / Self::CELL_SIZE).max(1.) as usize);
+        let (cell_w, cell_h) = (w*2. / cols as f64, h*2. / rows as f64);
+        self.cells = (cols, rows);
+        self.cell_size = (cell_w, cell_h);
+
+        // walls on the right and bottom side of every cell, removed by a depth first search from the top left cell
+        let mut right = vec![vec![true; rows]; cols];
+        let mut down = vec![vec![true; rows]; cols];
+        let mut visited = vec![vec![false; rows]; cols];
+        let mut stack = vec![(0, 0)];
+        visited[0][0] = true;
+        while let Some(&(c, r)) = stack.last() {
+            let mut neighbours = vec![];
+            if c > 0 && !visited[c-1][r] { neighbours.push((c-1, r)); }
+            if c+1 < cols && !visited[c+1][r] { neighbours.push((c+1, r)); }
+            if r > 0 && !visited[c][r-1] { neighbours.push((c, r-1)); }
+            if r+1 < rows && !visited[c][r+1] { neighbours.push((c, r+1)); }
+            let Some(&(nc, nr)) = neighbours.choose(&mut rng) else {
+                stack.pop();
+                continue
+            };
+            if nc != c {
+                right[c.min(nc)][r] = false;
+            } else {
+                down[c][r.min(nr)] = false;
+            }
+            visited[nc][nr] = true;
+            stack.push((nc, nr));
+        }
+
+        let t = Self::WALL_THICKNESS;
+        for c in 0..cols {
+            for r in 0..rows {
+                let (x, y) = (-w + c as f64 * cell_w, -h + r as f64 * cell_h);
+                if c+1 < cols && right[c][r] && !rng.gen_bool(Self::OPENNESS) {
+                    map.walls.push(Wall::rect(x + cell_w - t/2., y - t/2., t, cell_h + t));
+                }
+                if r+1 < rows && down[c][r] && !rng.gen_bool(Self::OPENNESS) {
+                    map.walls.push(Wall::rect(x - t/2., y + cell_h - t/2., cell_w + t, t));
+                }
+                // some cells get a rock in the middle
+                if rng.gen_bool(0.1) {
+                    let center = (x + cell_w/2., y + cell_h/2.);
+                    let size = cell_w.min(cell_h) / 6.;
+                    let angle: f64 = rng.gen::<f64>() * std::f64::consts::TAU;
+                    map.walls.push(Wall::polygon((0..3).map(|i| {
+                        let a = angle + i as f64 * std::f64::consts::TAU / 3.;
+                        (center.0 + a.cos()*size, center.1 + a.sin()*size)
+                    }).collect()));
+                }
+            }
+        }
+    }
+
+    fn spawn_position(&mut self, map: &Map, _id: u128) -> (f64, f64) {
+        // near the middle of a random cell, never inside a wall
+        let (c, r) = (thread_rng().gen_range(0..self.cells.0), thread_rng().gen_range(0..self.cells.1));
+        let (x, y) = (-map.map_size.0 + (c as f64 + 0.5) * self.cell_size.0, -map.map_size.1 + (r as f64 + 0.5) * self.cell_size.1);
+        let offset = self.cell_size.0.min(self.cell_size.1) / 4.;
+        let angle = thread_rng().gen::<f64>() * std::f64::consts::TAU;
+        (x + angle.cos()*offset, y + angle.sin()*offset)
+    }
+}
+
+/// For testing builds and learning classes: no bots unless turned on, unlimited xp, every class can be picked directly, and tools to spawn shapes and dummy targets.
+///
+/// The tools are used through `Gamemode::sandbox`, the local game binds them to keys
+pub struct Sandbox {
+    /// if the map is filled with bots like in FFA
+    pub bots: bool,
+    /// tanks that take no damage
+    invulnerable: HashSet<u128>,
+    /// nothing moves while frozen, see `Gamemode::paused`
+    pub frozen: bool,
+    /// max hp and hp regen of new dummies
+    pub dummy_hp: f64,
+    pub dummy_regen: f64,
+    /// dummy targets in the order they were spawned, with the damage they took as (seconds ago, damage)
+    dummies: Vec<(u128, Vec<(f64, f64)>)>,
+}
+impl Sandbox {
+    /// xp every tank has, enough for any class and all levels
+    const XP: f64 = 1_000_000.;
+    /// seconds of damage the dps of a dummy is averaged over
+    const DPS_WINDOW: f64 = 5.;
+
+    /// All classes of `EVOLUTION_TREE`, sorted by name
+    pub fn classes() -> Vec<String> {
+        let mut classes: Vec<String> = EVOLUTION_TREE.keys().cloned().collect();
+        classes.sort();
+        classes
+    }
+
+    /// Changes the class of the tank to the class `step` places after its current one in `classes`, ignoring the evolution path
+    pub fn cycle_class(&mut self, map: &mut Map, id: u128, step: isize) {
+        let Some(tank) = map.tanks.get_mut(&id) else {
+            return
+        };
+        let classes = Self::classes();
+        let current = classes.iter().position(|c| *c == tank.evolution.class).unwrap_or(0) as isize;
+        let class = classes[(current + step).rem_euclid(classes.len() as isize) as usize].clone();
+        // promoting costs xp, it is refilled on the next tick
+        tank.evolution.xp = Self::XP;
+        Evolution::promote(tank, class);
+    }
+
+    /// Adds a shape of a kind from `SHAPE_KINDS`
+    pub fn spawn_shape(&mut self, map: &mut Map, kind: &str, position: (f64, f64)) {
+        map.shapes.insert(thread_rng().gen::<u128>(), Shape::new(kind, 1., position));
+    }
+
+    /// Adds a basic tank that does nothing, with `dummy_hp` and `dummy_regen`. The damage it takes is shown in `status`
+    pub fn spawn_dummy(&mut self, map: &mut Map, position: (f64, f64)) {
+        let id = thread_rng().gen::<u128>();
+        map.spawn_tank(id, ROOT_CLASS);
+        let ph = &mut map.tanks.get_mut(&id).unwrap().physics;
+        ph.x = position.0;
+        ph.y = position.1;
+        ph.max_hp = self.dummy_hp;
+        ph.hp = self.dummy_hp;
+        ph.hp_regen = self.dummy_regen;
+        self.dummies.push((id, vec![]));
+    }
+
+    /// Makes the tank invulnerable, or vulnerable again if it already was
+    pub fn toggle_invulnerable(&mut self, id: u128) {
+        if !self.invulnerable.remove(&id) {
+            self.invulnerable.insert(id);
+        }
+    }
+}
+impl Default for Sandbox {
+    fn default() -> Self {
+        Sandbox {
+            bots: false,
+            invulnerable: HashSet::new(),
+            frozen: false,
+            dummy_hp: 1000.,
+            dummy_regen: 0.,
+            dummies: vec![],
+        }
+    }
+}
+impl Gamemode for Sandbox {
+    fn name(&self) -> &str {
+        "sandbox"
+    }
+
+    fn bot_count(&mut self, _map: &Map, bots: usize) -> usize {
+        if self.bots {bots} else {0}
+    }
+
+    fn on_tick(&mut self, map: &mut Map, delta: f64) {
+        let dummy_ids: HashSet<u128> = self.dummies.iter().map(|(id, _)| *id).collect();
+        for (id, tank) in map.tanks.iter_mut() {
+            if !dummy_ids.contains(id) {
+                tank.evolution.xp = tank.evolution.xp.max(Self::XP);
+            }
+        }
+
+        for (id, hits) in self.dummies.iter_mut() {
+            let Some(dummy) = map.tanks.get_mut(id) else {
+                continue
+            };
+            // only new damage is in `damage_from`, it is emptied every tick
+            let damage: f64 = dummy.damage_from.drain().map(|(_, d)| d).sum();
+            for hit in hits.iter_mut() {
+                hit.0 += delta;
+            }
+            hits.retain(|hit| hit.0 < Self::DPS_WINDOW);
+            if damage > 0. {
+                hits.push((0., damage));
+            }
+        }
+    }
+
+    fn on_tank_death(&mut self, _map: &mut Map, id: u128) {
+        self.invulnerable.remove(&id);
+        self.dummies.retain(|(dummy, _)| *dummy != id);
+    }
+
+    fn can_damage(&self, _map: &Map, _a: u128, b: u128) -> bool {
+        !self.invulnerable.contains(&b)
+    }
+
+    fn status(&self, _map: &Map) -> String {
+        let mut status = vec!["SANDBOX".to_owned()];
+        if self.frozen {
+            status.push("time frozen".to_owned());
+        }
+        if !self.invulnerable.is_empty() {
+            status.push("invulnerable".to_owned());
+        }
+        if self.bots {
+            status.push("bots on".to_owned());
+        }
+        for (_, hits) in &self.dummies {
+            status.push(format!("dummy: {:.1} dps", hits.iter().fold(0., |sum, hit| sum + hit.1) / Self::DPS_WINDOW));
+        }
+        status.join("  |  ")
+    }
+
+    fn paused(&self) -> bool {
+        self.frozen
+    }
+
+    fn sandbox(&mut self) -> Option<&mut Sandbox> {
+        Some(self)
+    }
+}
+
+/// What a `Waves` game is doing
+enum WavePhase {
+    /// seconds until the next wave, players spend their xp in the meantime
+    Break(f64),
+    /// until every enemy of the wave is dead or got into the base
+    Wave,
+}
+
+/// Players defend the base in the middle of the map against waves of AI tanks and crasher swarms. Every wave has more enemies, with more levels and classes deeper in the evolution tree.
+///
+/// Every enemy that gets into the base costs a life, the game starts over from the first wave once there are none left
+pub struct Waves {
+    phase: WavePhase,
+    /// the wave being played, or the last one during a break. 0 before the first wave
+    wave: usize,
+    lives: u32,
+    /// highest wave reached since the room was created
+    best_wave: usize,
+    /// tanks of the current wave that are still alive
+    enemies: HashSet<u128>,
+    /// shapes that chase players and the base
+    crashers: HashSet<u128>,
+    /// classes of `EVOLUTION_TREE` by how many promotions they are away from basic
+    classes_by_depth: Vec<Vec<String>>,
+}
+impl Waves {
+    const LIVES: u32 = 10;
+    /// seconds between waves
+    const BREAK_TIME: f64 = 15.;
+    const BASE_RADIUS: f64 = 300.;
+    /// enemies spawn this far from the base, or at the map border if the map is smaller
+    const SPAWN_DISTANCE: f64 = 3000.;
+    /// xp for every player per cleared wave, multiplied by the wave number
+    const WAVE_XP: f64 = 500.;
+    /// crashers chase players this close, otherwise they go for the base
+    const CRASHER_SIGHT: f64 = 1000.;
+    const CRASHER_ACCELERATION: f64 = 400.;
+    /// players are team 1, enemies team 2
+    const PLAYERS: u8 = 1;
+    const ENEMIES: u8 = 2;
+
+    /// A point on the ring where enemies spawn, clamped to the map
+    fn spawn_point(map: &Map, angle: f64) -> (f64, f64) {
+        (
+            (angle.cos() * Self::SPAWN_DISTANCE).clamp(-map.map_size.0, map.map_size.0),
+            (angle.sin() * Self::SPAWN_DISTANCE).clamp(-map.map_size.1, map.map_size.1),
+        )
+    }
+
+    fn in_base((x, y): (f64, f64)) -> bool {
+        x.powi(2) + y.powi(2) < Self::BASE_RADIUS.powi(2)
+    }
+
+    fn start_wave(&mut self, map: &mut Map) {
+        self.wave += 1;
+        self.best_wave = self.best_wave.max(self.wave);
+        self.phase = WavePhase::Wave;
+        map.announcements.push(format!("Wave {}", self.wave));
+
+        // a new depth every 3 waves, levels go up every 2 waves
+        let depth = ((self.wave - 1) / 3).min(self.classes_by_depth.len() - 1);
+        let level = (self.wave / 2).min(10) as u8;
+        for _ in 0..3 + self.wave * 2 {
+            let classes = &self.classes_by_depth[thread_rng().gen_range(0..=depth)];
+            let class = classes.choose(&mut thread_rng()).unwrap().clone();
+            let id = map.spawn_bot(&class);
+            map.tankais.last_mut().unwrap().objective = Some((0., 0.));
+            let position = Self::spawn_point(map, thread_rng().gen::<f64>() * std::f64::consts::TAU);
+            let tank = map.tanks.get_mut(&id).unwrap();
+            tank.team = Self::ENEMIES;
+            tank.physics.x = position.0;
+            tank.physics.y = position.1;
+            let ev = &mut tank.evolution;
+            ev.hp_level = level;
+            ev.regen_level = level;
+            ev.reload_level = level;
+            ev.damage_level = level;
+            ev.speed_level = level;
+            ev.bulletspeed_level = level;
+            Evolution::level_refresh(tank);
+            self.enemies.insert(id);
+        }
+
+        // the crashers come as one swarm, tougher every wave
+        let swarm = Self::spawn_point(map, thread_rng().gen::<f64>() * std::f64::consts::TAU);
+        for _ in 0..self.wave * 2 {
+            let position = (swarm.0 + thread_rng().gen_range(-300. ..300.), swarm.1 + thread_rng().gen_range(-300. ..300.));
+            let mut crasher = Shape::new("triangle", 1., position);
+            crasher.physics.max_hp *= 1. + self.wave as f64 * 0.25;
+            crasher.physics.hp = crasher.physics.max_hp;
+            crasher.physics.hp_regen /= 16.;
+            crasher.physics.weight = (crasher.physics.hp * 4096.).sqrt();
+            crasher.just_spawned_mode = false;
+            let id = thread_rng().gen::<u128>();
+            map.shapes.insert(id, crasher);
+            self.crashers.insert(id);
+        }
+    }
+
+    /// Removes what is left of the wave and starts over from the first one
+    fn game_over(&mut self, map: &mut Map) {
+        map.announcements.push(format!("Game over at wave {}, the best is wave {}", self.wave, self.best_wave));
+        for id in self.enemies.drain() {
+            map.tanks.remove(&id);
+        }
+        for id in self.crashers.drain() {
+            map.shapes.remove(&id);
+        }
+        self.wave = 0;
+        self.lives = Self::LIVES;
+        self.phase = WavePhase::Break(Self::BREAK_TIME);
+    }
+}
+impl Default for Waves {
+    fn default() -> Self {
+        // breadth first from basic, classes not reachable from it are left out
+        let mut classes_by_depth = vec![vec![ROOT_CLASS.to_owned()]];
+        let mut seen = HashSet::from([ROOT_CLASS.to_owned()]);
+        loop {
+            let next: Vec<String> = classes_by_depth.last().unwrap().iter()
+                .flat_map(|class| EVOLUTION_TREE[class].1.iter())
+                .filter(|class| EVOLUTION_TREE.contains_key(*class) && seen.insert((*class).clone()))
+                .cloned()
+                .collect();
+            if next.is_empty() {
+                break
+            }
+            classes_by_depth.push(next);
+        }
+
+        Waves {
+            phase: WavePhase::Break(Self::BREAK_TIME),
+            wave: 0,
+            lives: Self::LIVES,
+            best_wave: 0,
+            enemies: HashSet::new(),
+            crashers: HashSet::new(),
+            classes_by_depth,
+        }
+    }
+}
+impl Gamemode for Waves {
+    fn name(&self) -> &str {
+        "waves"
+    }
+
+    fn team_for(&mut self, _map: &Map, _id: u128) -> u8 {
+        Self::PLAYERS
+    }
+
+    fn spawn_position(&mut self, _map: &Map, _id: u128) -> (f64, f64) {
+        let angle = thread_rng().gen::<f64>() * std::f64::consts::TAU;
+        let dist = thread_rng().gen::<f64>() * Self::BASE_RADIUS;
+        (angle.cos() * dist, angle.sin() * dist)
+    }
+
+    fn bot_count(&mut self, _map: &Map, _bots: usize) -> usize {
+        // the enemies are spawned by the waves
+        0
+    }
+
+    fn on_tick(&mut self, map: &mut Map, delta: f64) {
+        if let WavePhase::Break(time) = &mut self.phase {
+            *time -= delta;
+            if *time <= 0. {
+                self.start_wave(map);
+            }
+            return
+        }
+
+        self.enemies.retain(|id| map.tanks.contains_key(id));
+        self.crashers.retain(|id| map.shapes.contains_key(id));
+
+        // crashers go for the closest player they see, or the base
+        let players: Vec<(f64, f64)> = map.tanks.values().filter(|t| t.team == Self::PLAYERS).map(|t| (t.physics.x, t.physics.y)).collect();
+        for id in &self.crashers {
+            let ph = &mut map.shapes.get_mut(id).unwrap().physics;
+            let dist = |p: &&(f64, f64)| (p.0 - ph.x).powi(2) + (p.1 - ph.y).powi(2);
+            let target = players.iter()
+                .filter(|p| dist(p) < Self::CRASHER_SIGHT.powi(2))
+                .min_by(|a, b| dist(a).partial_cmp(&dist(b)).unwrap())
+                .copied()
+                .unwrap_or((0., 0.));
+            let dir = normalize((target.0 - ph.x, target.1 - ph.y));
+            let force = Self::CRASHER_ACCELERATION * ph.weight * delta;
+            ph.push((dir.0 * force, dir.1 * force));
+        }
+
+        // every enemy in the base costs a life
+        let enemies_in: Vec<u128> = self.enemies.iter().filter(|id| Self::in_base((map.tanks[id].physics.x, map.tanks[id].physics.y))).copied().collect();
+        let crashers_in: Vec<u128> = self.crashers.iter().filter(|id| Self::in_base((map.shapes[id].physics.x, map.shapes[id].physics.y))).copied().collect();
+        for id in &enemies_in {
+            map.tanks.remove(id);
+            self.enemies.remove(id);
+        }
+        for id in &crashers_in {
+            map.shapes.remove(id);
+            self.crashers.remove(id);
+        }
+        self.lives = self.lives.saturating_sub((enemies_in.len() + crashers_in.len()) as u32);
+
+        if self.lives == 0 {
+            self.game_over(map);
+        } else if self.enemies.is_empty() && self.crashers.is_empty() {
+            map.announcements.push(format!("Wave {} cleared", self.wave));
+            for tank in map.tanks.values_mut().filter(|t| t.team == Self::PLAYERS) {
+                tank.evolution.xp += Self::WAVE_XP * self.wave as f64;
+            }
+            self.phase = WavePhase::Break(Self::BREAK_TIME);
+        }
+    }
+
+    fn on_tank_death(&mut self, _map: &mut Map, id: u128) {
+        self.enemies.remove(&id);
+    }
+
+    fn can_damage(&self, map: &Map, a: u128, b: u128) -> bool {
+        let team = map.team_of(&a);
+        team == 0 || team != map.team_of(&b)
+    }
+
+    fn status(&self, _map: &Map) -> String {
+        match self.phase {
+            WavePhase::Break(time) => format!("Wave {} in {:.0}  |  lives: {}  |  best wave: {}", self.wave + 1, time.ceil(), self.lives, self.best_wave),
+            WavePhase::Wave => format!("Wave {}  |  enemies: {}  |  lives: {}  |  best wave: {}", self.wave, self.enemies.len() + self.crashers.len(), self.lives, self.best_wave),
+        }
+    }
+
+    fn render(&self, canvas: &mut Canvas<Window>, camera: &Camera) {
+        let zoom = camera.zoom * ((camera.viewport_size.0.pow(2) + camera.viewport_size.1.pow(2)) as f64).sqrt() / 1024.;
+        draw_circle_outline(canvas, camera.to_screen_coords((0., 0.)), Self::BASE_RADIUS * zoom, team_color(Self::PLAYERS));
+    }
+}
+
+enum InfectionPhase {
+    /// seconds until the round starts. Tanks respawn and deal no damage
+    Lobby(f64),
+    /// seconds until the survivors win
+    Running(f64),
+    /// seconds until the next lobby
+    Ended(f64),
+}
+
+/// A few tanks start infected. Every survivor killed by the infected comes back infected, as an infector of the infected team. Survivors that die to anything else come back as survivors.
+/// The survivors win if any of them is still alive when the time runs out, the infected win if they get everyone.
+///
+/// Infected bots hunt the closest survivor, survivor bots run from the infected
+pub struct Infection {
+    phase: InfectionPhase,
+    /// tanks that were never infected this round. Everyone else is infected, players that join late too
+    survivors: HashSet<u128>,
+    /// infected tanks that already got the infected class
+    converted: HashSet<u128>,
+    /// survivor players that died to something else than the infected and did not respawn yet
+    respawning: HashSet<u128>,
+    winner: Option<String>,
+}
+impl Infection {
+    const LOBBY_TIME: f64 = 10.;
+    const ROUND_TIME: f64 = 180.;
+    const RESULTS_TIME: f64 = 8.;
+    /// one in this many tanks starts infected, at least one
+    const FIRST_INFECTED: usize = 10;
+    const SURVIVORS: u8 = 1;
+    const INFECTED: u8 = 3;
+    const INFECTED_CLASS: &'static str = "infector";
+    /// infected bots see survivors this far away
+    const HUNTER_RANGE: f64 = 6144.;
+
+    fn end_round(&mut self, winner: &str) {
+        println!("Infection round over, the {} won", winner);
+        self.winner = Some(winner.to_owned());
+        self.phase = InfectionPhase::Ended(Self::RESULTS_TIME);
+    }
+}
+impl Default for Infection {
+    fn default() -> Self {
+        Infection {
+            phase: InfectionPhase::Lobby(Self::LOBBY_TIME),
+            survivors: HashSet::new(),
+            converted: HashSet::new(),
+            respawning: HashSet::new(),
+            winner: None,
+        }
+    }
+}
+impl Gamemode for Infection {
+    fn name(&self) -> &str {
+        "infection"
+    }
+
+    fn team_for(&mut self, _map: &Map, id: u128) -> u8 {
+        self.respawning.remove(&id);
+        match self.phase {
+            InfectionPhase::Running(_) if !self.survivors.contains(&id) => Self::INFECTED,
+            _ => Self::SURVIVORS,
+        }
+    }
+
+    fn on_tick(&mut self, map: &mut Map, delta: f64) {
+        match self.phase {
+            InfectionPhase::Lobby(time) => {
+                if time - delta > 0. {
+                    self.phase = InfectionPhase::Lobby(time - delta);
+                    return
+                }
+                // a round needs someone to infect
+                if map.tanks.len() < 2 {
+                    self.phase = InfectionPhase::Lobby(Self::LOBBY_TIME);
+                    return
+                }
+                self.survivors = map.tanks.keys().copied().collect();
+                let ids: Vec<u128> = self.survivors.iter().copied().collect();
+                for id in ids.choose_multiple(&mut thread_rng(), (ids.len() / Self::FIRST_INFECTED).max(1)) {
+                    self.survivors.remove(id);
+                    map.tanks.get_mut(id).unwrap().team = Self::INFECTED;
+                }
+                map.announcements.push("The infection has started".to_owned());
+                self.phase = InfectionPhase::Running(Self::ROUND_TIME);
+            }
+            InfectionPhase::Running(time) => {
+                let respawning = &self.respawning;
+                self.survivors.retain(|id| map.tanks.contains_key(id) || respawning.contains(id));
+
+                // the infected change their class once, after that they can upgrade it like any tank
+                for (id, tank) in map.tanks.iter_mut() {
+                    if tank.team == Self::INFECTED && self.converted.insert(*id) {
+                        tank.evolution.xp += EVOLUTION_TREE[Self::INFECTED_CLASS].2;
+                        Evolution::promote(tank, Self::INFECTED_CLASS.to_owned());
+                    }
+                }
+
+                for ai in map.tankais.iter_mut() {
+                    if self.survivors.contains(&ai.id) {
+                        ai.temper = Temper::Prey;
+                    } else {
+                        ai.temper = Temper::Hunter;
+                        ai.range = Self::HUNTER_RANGE;
+                    }
+                }
+
+                if self.survivors.is_empty() {
+                    self.end_round("infected");
+                } else if time - delta <= 0. {
+                    self.end_round("survivors");
+                } else {
+                    self.phase = InfectionPhase::Running(time - delta);
+                }
+            }
+            InfectionPhase::Ended(time) => {
+                if time - delta > 0. {
+                    self.phase = InfectionPhase::Ended(time - delta);
+                    return
+                }
+                // reset into the next round, everyone gets a new tank in the lobby
+                map.tanks.clear();
+                map.tankais.clear();
+                map.bullets.clear();
+                self.survivors.clear();
+                self.converted.clear();
+                self.respawning.clear();
+                self.winner = None;
+                self.phase = InfectionPhase::Lobby(Self::LOBBY_TIME);
+            }
+        }
+    }
+
+    fn on_kill(&mut self, map: &mut Map, killer: u128, victim: u128) {
+        // the next tank of a survivor killed by the infected is infected
+        if matches!(self.phase, InfectionPhase::Running(_)) && map.team_of(&killer) == Self::INFECTED {
+            self.survivors.remove(&victim);
+        }
+    }
+
+    fn on_tank_death(&mut self, map: &mut Map, id: u128) {
+        // infected players get the infected class again
+        self.converted.remove(&id);
+        // bots don't come back, survivor players respawn as survivors
+        if self.survivors.contains(&id) {
+            if map.tankais.iter().any(|ai| ai.id == id) {
+                self.survivors.remove(&id);
+            } else {
+                self.respawning.insert(id);
+            }
+        }
+    }
+
+    fn can_damage(&self, map: &Map, a: u128, b: u128) -> bool {
+        let team = map.team_of(&a);
+        matches!(self.phase, InfectionPhase::Running(_)) && (team == 0 || team != map.team_of(&b))
+    }
+
+    fn winner(&self, _map: &Map) -> Option<String> {
+        self.winner.clone()
+    }
+
+    fn status(&self, map: &Map) -> String {
+        match self.phase {
+            InfectionPhase::Lobby(time) => format!("Infection starts in {:.0}", time.ceil()),
+            InfectionPhase::Running(time) => format!(
+                "{} survivors, {} infected  |  {:.0}s left",
+                self.survivors.len(), map.tanks.values().filter(|t| t.team == Self::INFECTED).count(), time.ceil()
+            ),
+            InfectionPhase::Ended(_) => format!("The {} won", self.winner.as_deref().unwrap_or("nobody")),
+        }
+    }
+}
+
+/// Where a `Flag` is
+#[derive(Clone, Copy, PartialEq)]
+enum FlagState {
+    Home,
+    /// by the tank with this id
+    Carried(u128),
+    /// on the ground where the carrier died, goes home after this many seconds
+    Dropped(f64),
+}
+
+struct Flag {
+    team: u8,
+    home: (f64, f64),
+    position: (f64, f64),
+    state: FlagState,
+}
+
+/// Capture the flag for 2 teams. Every base has a flag, enemies pick it up by touching it. Carrying a flag slows the tank down, and it is dropped when the carrier dies.
+///
+/// Bringing the enemy flag to your own flag while that is home scores a capture, the first team to `Ctf::CAPTURES_TO_WIN` wins
+pub struct Ctf {
+    /// team assignment, bases and friendly fire work like in team deathmatch
+    teams: Teams,
+    /// placed on the first frame, when the map size is known. Index 0 is the flag of team 1
+    flags: Vec<Flag>,
+    /// index 0 is team 1
+    captures: Vec<u32>,
+}
+impl Ctf {
+    const CAPTURES_TO_WIN: u32 = 3;
+    /// tanks closer than this to a flag touch it
+    const FLAG_RADIUS: f64 = 60.;
+    /// a captured flag has to be brought this close to the home of the own flag
+    const HOME_RADIUS: f64 = 250.;
+    /// seconds a dropped flag stays on the ground
+    const RETURN_TIME: f64 = 30.;
+    /// extra friction of a tank carrying a flag
+    const CARRIER_DRAG: f64 = 1.5;
+
+    pub fn new() -> Self {
+        Ctf {
+            teams: Teams::new(2),
+            flags: vec![],
+            captures: vec![0; 2],
+        }
+    }
+
+    fn state_name(state: FlagState) -> &'static str {
+        match state {
+            FlagState::Home => "home",
+            FlagState::Carried(_) => "taken",
+            FlagState::Dropped(_) => "dropped",
+        }
+    }
+}
+impl Gamemode for Ctf {
+    fn name(&self) -> &str {
+        "ctf"
+    }
+
+    fn team_for(&mut self, map: &Map, id: u128) -> u8 {
+        self.teams.team_for(map, id)
+    }
+
+    fn spawn_position(&mut self, map: &Map, id: u128) -> (f64, f64) {
+        self.teams.spawn_position(map, id)
+    }
+
+    fn on_tick(&mut self, map: &mut Map, delta: f64) {
+        if self.flags.is_empty() {
+            let w = map.map_size.0;
+            self.flags = [(1, (-w*0.9, 0.)), (2, (w*0.9, 0.))].into_iter().map(|(team, home)| Flag {
+                team,
+                home,
+                position: home,
+                state: FlagState::Home,
+            }).collect();
+        }
+
+        for i in 0..self.flags.len() {
+            let flag = &mut self.flags[i];
+            match flag.state {
+                FlagState::Carried(carrier) => match map.tanks.get_mut(&carrier) {
+                    Some(tank) => {
+                        tank.physics.xvel *= (-delta * Self::CARRIER_DRAG).exp();
+                        tank.physics.yvel *= (-delta * Self::CARRIER_DRAG).exp();
+                        flag.position = (tank.physics.x, tank.physics.y);
+                    }
+                    // the carrier left the room
+                    None => flag.state = FlagState::Dropped(Self::RETURN_TIME),
+                },
+                FlagState::Dropped(time) => {
+                    flag.state = if time - delta <= 0. {
+                        flag.position = flag.home;
+                        FlagState::Home
+                    } else {
+                        FlagState::Dropped(time - delta)
+                    };
+                }
+                FlagState::Home => {}
+            }
+
+            // enemies pick up the flag, the own team returns it when it is dropped. A tank carries one flag at most
+            if let FlagState::Home | FlagState::Dropped(_) = flag.state {
+                let (team, position) = (flag.team, flag.position);
+                let carriers: Vec<FlagState> = self.flags.iter().map(|f| f.state).collect();
+                let toucher = map.tanks.iter()
+                    .filter(|(_, t)| t.team != 0 && (t.physics.x - position.0).powi(2) + (t.physics.y - position.1).powi(2) < (Self::FLAG_RADIUS + t.physics.collision_size).powi(2))
+                    .filter(|(id, t)| t.team == team || !carriers.contains(&FlagState::Carried(**id)))
+                    .map(|(id, t)| (*id, t.team))
+                    // enemies first, a defender on the flag does not protect it
+                    .min_by_key(|(_, t)| *t == team);
+                let flag = &mut self.flags[i];
+                match toucher {
+                    Some((id, team)) if team != flag.team => {
+                        flag.state = FlagState::Carried(id);
+                        map.announcements.push(format!("Team {} took the flag of team {}", team, flag.team));
+                    }
+                    Some(_) if flag.state != FlagState::Home => {
+                        flag.state = FlagState::Home;
+                        flag.position = flag.home;
+                        map.announcements.push(format!("The flag of team {} is back home", flag.team));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // a carried flag scores when it gets to the home of the carrier's flag, while that flag is there
+        for i in 0..self.flags.len() {
+            let FlagState::Carried(carrier) = self.flags[i].state else {
+                continue
+            };
+            let team = map.team_of(&carrier);
+            let Some(own) = self.flags.iter().find(|f| f.team == team) else {
+                continue
+            };
+            let at_home = (self.flags[i].position.0 - own.home.0).powi(2) + (self.flags[i].position.1 - own.home.1).powi(2) < Self::HOME_RADIUS.powi(2);
+            if own.state == FlagState::Home && at_home {
+                self.captures[team as usize - 1] += 1;
+                map.announcements.push(format!("Team {} captured the flag of team {}", team, self.flags[i].team));
+                let flag = &mut self.flags[i];
+                flag.state = FlagState::Home;
+                flag.position = flag.home;
+            }
+        }
+
+        // bots carrying a flag bring it home, the others go for the enemy flag, or chase the own flag if it was taken
+        for ai in map.tankais.iter_mut() {
+            let Some(tank) = map.tanks.get(&ai.id) else {
+                continue
+            };
+            let own = self.flags.iter().find(|f| f.team == tank.team);
+            ai.objective = if self.flags.iter().any(|f| f.state == FlagState::Carried(ai.id)) {
+                own.map(|f| f.home)
+            } else if own.is_some_and(|f| f.state != FlagState::Home) {
+                own.map(|f| f.position)
+            } else {
+                self.flags.iter().find(|f| f.team != tank.team).map(|f| f.position)
+            };
+        }
+    }
+
+    fn on_kill(&mut self, map: &mut Map, killer: u128, victim: u128) {
+        self.teams.on_kill(map, killer, victim);
+    }
+
+    fn on_tank_death(&mut self, map: &mut Map, id: u128) {
+        self.teams.on_tank_death(map, id);
+        for flag in self.flags.iter_mut().filter(|f| f.state == FlagState::Carried(id)) {
+            flag.state = FlagState::Dropped(Self::RETURN_TIME);
+            map.announcements.push(format!("The flag of team {} was dropped", flag.team));
+        }
+    }
+
+    fn can_damage(&self, map: &Map, a: u128, b: u128) -> bool {
+        self.teams.can_damage(map, a, b)
+    }
+
+    fn winner(&self, _map: &Map) -> Option<String> {
+        self.captures.iter().position(|c| *c >= Self::CAPTURES_TO_WIN).map(|i| format!("team {}", i + 1))
+    }
+
+    fn status(&self, _map: &Map) -> String {
+        let teams: Vec<String> = self.flags.iter().map(|f| {
+            format!("team {}: {} captures, flag {}", f.team, self.captures[f.team as usize - 1], Self::state_name(f.state))
+        }).collect();
+        teams.join("  |  ")
+    }
+
+    fn render(&self, canvas: &mut Canvas<Window>, camera: &Camera) {
+        let zoom = camera.zoom * ((camera.viewport_size.0.pow(2) + camera.viewport_size.1.pow(2)) as f64).sqrt() / 1024.;
+        for flag in &self.flags {
+            let color = team_color(flag.team);
+            draw_circle_outline(canvas, camera.to_screen_coords(flag.home), Self::HOME_RADIUS * zoom, color);
+
+            let (x, y) = camera.to_screen_coords(flag.position);
+            let radius = (Self::FLAG_RADIUS * zoom).min(i16::MAX as f64) as i16;
+            // flags off the screen are shown at the border, in the direction they are
+            let margin = 30;
+            let on_screen = x >= 0 && y >= 0 && x < camera.viewport_size.0 && y < camera.viewport_size.1;
+            let (x, y, radius) = if on_screen {
+                (x, y, radius)
+            } else {
+                (x.clamp(margin, camera.viewport_size.0 - margin), y.clamp(margin, camera.viewport_size.1 - margin), 16)
+            };
+            canvas.filled_circle(x as i16, y as i16, radius, color).unwrap();
+            // the outline shows if the flag is home, taken or dropped
+            let outline = match flag.state {
+                FlagState::Home => color,
+                FlagState::Carried(_) => Color::WHITE,
+                FlagState::Dropped(_) => Color::GRAY,
+            };
+            canvas.circle(x as i16, y as i16, radius + 4, outline).unwrap();
+        }
+    }
+}
@@ -1,4 +1,10 @@
-use network::run_network;
+use network::{InputOverNetwork, NetworkServer};
+use room::{Room, RoomSettings};
+use gamemode::Gamemode;
+use zone::Zone;
+use boss::BossAI;
+use walls::Wall;
+use serde::{Deserialize, Serialize};
 use rand::prelude::*;
 use rand_distr::Distribution;
 use rand_distr::num_traits::Pow;
@@ -10,9 +16,9 @@ use sdl2::libc::time;
 use sdl2::mouse::MouseButton;
 use sdl2::pixels::Color;
 use sdl2::rect::{Point, Rect};
-use sdl2::render::{Canvas, Texture, TextureCreator};
+use sdl2::render::{BlendMode, Canvas, Texture, TextureCreator};
 use sdl2::video::Window;
-use tank_tree::EVOLUTION_TREE;
+use tank_tree::{EVOLUTION_TREE, AiHints};
 use std::collections::{HashMap, HashSet};
 use std::io::{Read, Write};
 use std::sync::mpsc;
@@ -23,14 +29,33 @@ use std::error::Error;
 
 mod tank_tree;
 mod network;
+mod netsim;
+mod room;
+mod gamemode;
+mod zone;
+mod boss;
+mod walls;
+
+/// Color of the tanks of a team. Tanks without a team are blue
+fn team_color(team: u8) -> Color {
+    match team {
+        1 => Color::RGB(0, 178, 225),
+        2 => Color::RGB(241, 78, 84),
+        3 => Color::RGB(0, 225, 110),
+        4 => Color::RGB(191, 127, 245),
+        boss::BOSS_TEAM => Color::RGB(255, 200, 0),
+        _ => Color::RGB(0, 0, 255),
+    }
+}
 
-const GAMEMODE: Gamemode = Gamemode::FFA;
-
-#[derive(PartialEq, Eq)]
-enum Gamemode {
-    FFA,
-    Survival,
-
+/// Draws a circle out of lines, works for circles much bigger than the screen
+fn draw_circle_outline(canvas: &mut Canvas<Window>, center: (i32, i32), radius: f64, color: Color) {
+    let points: Vec<Point> = (0..=128).map(|i| {
+        let angle = i as f64 / 128. * std::f64::consts::TAU;
+        Point::new(center.0 + (angle.cos()*radius) as i32, center.1 + (angle.sin()*radius) as i32)
+    }).collect();
+    canvas.set_draw_color(color);
+    canvas.draw_lines(points.as_slice()).unwrap();
 }
 
 /// From A to B, in radians
@@ -121,26 +146,30 @@ impl Physics {
     }
 
     /// Only moves self, need to be called in reverse to move `b`
-    fn collide(&mut self, b: &Physics, delta: f64) {
+    ///
+    /// `b_body_damage` multiplies the damage `b` deals, `knockback_resistance` (0 to 1) is the part of the push self ignores. See `Tank::body_damage`
+    fn collide(&mut self, b: &Physics, delta: f64, b_body_damage: f64, knockback_resistance: f64) {
         let speed_diff = vector_lenght(vector_diff((b.xvel, b.yvel), (self.xvel, self.yvel)));
         let s = delta*(speed_diff+64.)*8.;
         self.xvel *= (-delta*4.).exp();
         self.yvel *= (-delta*4.).exp();
         let n = normalize((self.x - b.x, self.y - b.y));
-        self.push((n.0*s*((b.weight.sqrt()+self.weight.sqrt())), n.1*s*((b.weight.sqrt()+self.weight.sqrt()))));
-        self.hp -= (s/16.).min(b.hp);
+        let k = 1. - knockback_resistance;
+        self.push((n.0*s*((b.weight.sqrt()+self.weight.sqrt()))*k, n.1*s*((b.weight.sqrt()+self.weight.sqrt()))*k));
+        self.hp -= (s/16.).min(b.hp)*b_body_damage;
         self.push_rot(delta*speed_diff*angle_diff(f64::atan2(b.x - self.x, b.y - self.y).to_degrees(), f64::atan2((b.x + b.xvel) - (self.x + self.xvel), (b.y + b.yvel) - (self.y + self.yvel)).to_degrees())/-1.);
         
     }
 
     /// same as collide, but does not affect HP
-    fn collide_position_only(&mut self, b: &Physics, delta: f64) {
+    fn collide_position_only(&mut self, b: &Physics, delta: f64, knockback_resistance: f64) {
         let speed_diff = vector_lenght(vector_diff((b.xvel, b.yvel), (self.xvel, self.yvel)));
         let s = delta*(speed_diff+64.)*8.;
         self.xvel *= (-delta*4.).exp();
         self.yvel *= (-delta*4.).exp();
         let n = normalize((self.x - b.x, self.y - b.y));
-        self.push((n.0*s*((b.weight.sqrt()+self.weight.sqrt())), n.1*s*((b.weight.sqrt()+self.weight.sqrt()))));
+        let k = 1. - knockback_resistance;
+        self.push((n.0*s*((b.weight.sqrt()+self.weight.sqrt()))*k, n.1*s*((b.weight.sqrt()+self.weight.sqrt()))*k));
         self.push_rot(delta*speed_diff*angle_diff(f64::atan2(b.x - self.x, b.y - self.y).to_degrees(), f64::atan2((b.x + b.xvel) - (self.x + self.xvel), (b.y + b.yvel) - (self.y + self.yvel)).to_degrees())/-1.);
         
     }
@@ -160,6 +189,9 @@ impl Physics {
     }
 }
 
+/// Textures of the kinds of shapes, see `Shape::new`
+const SHAPE_KINDS: [&str; 4] = ["square", "triangle", "hexagon", "12gon"];
+
 /// Square, triangle, pentagon, 12gon
 struct Shape {
     physics: Physics,
@@ -169,6 +201,60 @@ struct Shape {
     just_spawned_mode: bool
 }
 impl Shape {
+    /// A just spawned shape of a kind from `SHAPE_KINDS`. `size` is from 0.8 to 1.2 for normal shapes, hexagons and 12gons are made bigger from it
+    fn new(kind: &str, size: f64, (x, y): (f64, f64)) -> Self {
+        let is_12gon = kind == "12gon";
+        let is_hexagon = kind == "hexagon" || is_12gon;
+        let is_triangle = kind == "triangle";
+        let size = if is_12gon {
+            size * 12.
+        } else if is_hexagon {
+            size * 4.
+        } else if is_triangle {
+            size * 1.2
+        } else {
+            size
+        };
+        Shape {
+            physics: Physics {
+                x,
+                y,
+                xvel: 0.,
+                yvel: 0.,
+                weight: 1.,
+                rot: thread_rng().gen::<f64>()*360.,
+                rotvel: 0.,
+                collision_size: 20. * size,
+                hp: 4.,
+                max_hp: if is_hexagon {
+                    if is_12gon {
+                        300. * size.powi(2)
+                    } else {
+                        30. * size.powi(2)
+                    }
+                } else if is_triangle{
+                    2.0 * size.powi(2)
+                } else {
+                    10. * size.powi(2)
+                },
+                // hp regen is multiplied by 64, because it is later divided by 64 when shape reaches full hp.
+                hp_regen: 16. *
+                if is_hexagon {
+                    if is_12gon {
+                        1. * size.powi(2)
+                    } else {
+                        0.15 * size.powi(2)
+                    }
+                } else if is_triangle{
+                    3. * size.powi(2)
+                } else {
+                    0.5 * size.powi(2)
+                },
+            },
+            texture: kind.to_owned(),
+            just_spawned_mode: true,
+        }
+    }
     fn render(&self, canvas: &mut Canvas<Window>, camera: &Camera, textures: &HashMap<String, Texture>) {
         let rendersize = self.physics.collision_size*4.*camera.zoom*((camera.viewport_size.0.pow(2)+camera.viewport_size.1.pow(2)) as f64).sqrt()/1024.;
         let texture = &textures.get(&self.texture).unwrap();
@@ -195,7 +281,8 @@ impl Shape {
 }
 
 /// Turrets can now only shoot bullets, will change later
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 struct Turret {
     /// should be about 1000x the weight for normal speed
     projectile_impulse: f64,
@@ -205,8 +292,9 @@ struct Turret {
     projectile_hp_regen: f64,
     /// also the max damage
     projectile_hp: f64,
-    /// affects projectile type, like bullet, bomb drone, trap etc
+    /// only how the projectiles look, what they do is `projectile_kind`
     projectile_texture: String,
+    projectile_kind: ProjectileKind,
     /// in micros, first shot is immediatae
     /// 
     /// should be >0.033 (30 shots per second), because more shots/second than fps makes glitches
@@ -218,10 +306,43 @@ struct Turret {
     relative_direction: f64,
     /// position where the bullet spawns
     relative_position: (f64, f64),
+    /// turns and fires on its own at the closest enemy in `auto_range` instead of firing with the tank, see `Map::aim_auto_turrets`.
+    /// `relative_position` is then where the turret sits on the tank
+    auto: bool,
+    /// how far an auto turret sees targets
+    auto_range: f64,
+    /// in degrees, how far an auto turret can turn from `relative_direction` to each side. 180 to turn all the way around
+    auto_arc: f64,
+    /// 0 to 1, part of `reload_time` the turret waits before its first shot when the tank starts firing. Barrels with different delays fire in turns
+    delay: f64,
+    /// turrets of the same group (above 0) take turns: each shot of the group comes from the next turret of it, after the reload of the last one. See `Tank::fire`
+    group: u32,
+    /// part of the fire impulse that pushes the tank back on each shot. Barrels facing backwards push the tank forward
+    recoil: f64,
+    /// makes the projectiles rockets with their own engine, see `Rocket`
+    rocket: Option<Rocket>,
+    /// fires a beam instead of projectiles, see `Laser`
+    laser: Option<Laser>,
 
     // start of changing properties
 
-    time_to_next_shot: f64
+    #[serde(skip)]
+    time_to_next_shot: f64,
+    /// in degrees, how far an auto turret is turned from `relative_direction`
+    #[serde(skip)]
+    aim: f64,
+    /// an auto turret only fires while it has a target
+    #[serde(skip)]
+    has_target: bool,
+    /// the tank tried to fire this turret since the last reload update, see `Turret::pull_trigger`
+    #[serde(skip)]
+    triggered: bool,
+    /// the tank stopped firing, so the next shot waits for the `delay` again
+    #[serde(skip)]
+    idle: bool,
+    /// seconds a laser has been charging for
+    #[serde(skip)]
+    charged: f64,
 }
 impl Default for Turret {
     fn default() -> Self {
@@ -232,18 +353,33 @@ impl Default for Turret {
             projectile_hp_regen: -1.,
             projectile_hp: 1.,
             projectile_texture: "bullet".to_string(),
+            projectile_kind: ProjectileKind::Bullet,
             reload_time: 1.,
             inaccuracy: 0.,
             relative_direction: 0.,
             relative_position: (0.,-100.),
+            auto: false,
+            auto_range: 1024.,
+            auto_arc: 180.,
+            delay: 0.,
+            group: 0,
+            recoil: 0.,
+            rocket: None,
+            laser: None,
             time_to_next_shot: 0.,
+            aim: 0.,
+            has_target: false,
+            triggered: false,
+            idle: true,
+            charged: 0.,
         }
     }
 }
 impl Turret {
     /// Returns an Option<Bullet> if fired, and None otherwise.
-    /// Tank physics can be physics of anything, theoretically allowing bullets of shapes to fire bullets too if they have a turret
-    fn fire(&mut self, tank_physics: &Physics, tank_id: u128) -> Option<Bullet> {
+    /// Tank physics can be physics of anything, theoretically allowing bullets of shapes to fire bullets too if they have a turret.
+    /// The tank gets pushed back by the `recoil`
+    fn fire(&mut self, tank_physics: &mut Physics, tank_id: u128) -> Option<Bullet> {
         if self.time_to_next_shot > 0. {
             None
         } else {
@@ -263,8 +399,8 @@ impl Turret {
 
             // calculate bullet speed vector relative to tank
             let fire_vector = (
-                random_speed * (self.relative_direction+tank_physics.rot + random_direction).to_radians().sin(),
-                -random_speed* (self.relative_direction+tank_physics.rot + random_direction).to_radians().cos()
+                random_speed * (self.direction(tank_physics) + random_direction).to_radians().sin(),
+                -random_speed* (self.direction(tank_physics) + random_direction).to_radians().cos()
             );
 
             // duplicate tank physics
@@ -274,23 +410,199 @@ impl Turret {
             bullet_physics.weight = self.projectile_weight;
             bullet_physics.collision_size = self.projectile_collision_size;
             bullet_physics.push(fire_vector);
-            bullet_physics.x += (self.relative_direction+tank_physics.rot).to_radians().cos()*(self.relative_position.0);
-            bullet_physics.x -= (self.relative_direction+tank_physics.rot).to_radians().sin()*(self.relative_position.1);
-            bullet_physics.y += (self.relative_direction+tank_physics.rot).to_radians().sin()*(self.relative_position.0);
-            bullet_physics.y += (self.relative_direction+tank_physics.rot).to_radians().cos()*(self.relative_position.1);
+            (bullet_physics.x, bullet_physics.y) = self.mount(tank_physics);
+            // auto turrets fire from the end of their barrel
+            if self.auto {
+                let barrel = self.barrel_length();
+                bullet_physics.x += self.direction(tank_physics).to_radians().sin()*barrel;
+                bullet_physics.y -= self.direction(tank_physics).to_radians().cos()*barrel;
+            }
             bullet_physics.hp_regen = self.projectile_hp_regen;
             bullet_physics.hp = self.projectile_hp;
             bullet_physics.max_hp = self.projectile_hp;
 
+            // rockets face where they fly, to push themselves that way
+            if self.rocket.is_some() {
+                bullet_physics.rot = self.direction(tank_physics) + random_direction;
+                bullet_physics.rotvel = 0.;
+            }
+
+            tank_physics.push((-fire_vector.0*self.recoil, -fire_vector.1*self.recoil));
+
             self.time_to_next_shot = self.reload_time;
 
             Some(Bullet {
                 physics: bullet_physics,
                 source_tank_id: tank_id,
-                texture: self.projectile_texture.to_owned()
+                // set by the tank
+                team: 0,
+                texture: self.projectile_texture.to_owned(),
+                kind: self.projectile_kind,
+                rocket: self.rocket.clone(),
             })
         }
     }
+
+    /// Called every frame the tank is firing, before `fire`. The first shot after the tank was not firing waits for the `delay`
+    fn pull_trigger(&mut self) {
+        if self.idle {
+            self.idle = false;
+            self.time_to_next_shot = self.time_to_next_shot.max(self.delay*self.reload_time);
+        }
+        self.triggered = true;
+    }
+
+    /// Map coordinates of `relative_position`, where the bullets of normal turrets spawn and where auto turrets sit
+    fn mount(&self, tank_physics: &Physics) -> (f64, f64) {
+        let rot = (self.relative_direction+tank_physics.rot).to_radians();
+        (
+            tank_physics.x + rot.cos()*self.relative_position.0 - rot.sin()*self.relative_position.1,
+            tank_physics.y + rot.sin()*self.relative_position.0 + rot.cos()*self.relative_position.1,
+        )
+    }
+
+    /// In degrees on the map, where the turret fires to
+    fn direction(&self, tank_physics: &Physics) -> f64 {
+        self.relative_direction + self.aim + tank_physics.rot
+    }
+
+    /// Length of the barrel of an auto turret, from its mount
+    fn barrel_length(&self) -> f64 {
+        self.projectile_collision_size * 2.5
+    }
+}
+
+/// What a projectile does, besides flying and hitting things. Set per turret in the class files like `{"type": "trap"}`
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ProjectileKind {
+    Bullet,
+    /// steered by its tank, see `DroneControl`
+    Drone,
+    /// slows down fast and blocks other projectiles
+    Trap,
+    /// blocks other projectiles and bursts when it dies
+    Bomb(Bomb),
+    /// a bomb that sticks to what it touches
+    MagnetBomb(Bomb),
+    /// a bomb that slows down like a trap and bursts into traps
+    TrapBomb(Bomb),
+}
+impl ProjectileKind {
+    /// The burst of bombs
+    fn bomb(&self) -> Option<&Bomb> {
+        match self {
+            ProjectileKind::Bomb(bomb) | ProjectileKind::MagnetBomb(bomb) | ProjectileKind::TrapBomb(bomb) => Some(bomb),
+            _ => None,
+        }
+    }
+
+    /// Other projectiles bounce off it instead of flying through
+    fn blocks_projectiles(&self) -> bool {
+        matches!(self, ProjectileKind::Trap | ProjectileKind::Bomb(_) | ProjectileKind::TrapBomb(_))
+    }
+
+    /// Slows down 4x faster than bullets
+    fn slows_down(&self) -> bool {
+        matches!(self, ProjectileKind::Trap | ProjectileKind::TrapBomb(_))
+    }
+
+    /// Slides along walls instead of dying on them
+    fn slides_on_walls(&self) -> bool {
+        matches!(self, ProjectileKind::Drone | ProjectileKind::Trap)
+    }
+}
+
+/// How a bomb bursts when it dies, see `ProjectileKind`
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Bomb {
+    /// how many projectiles it bursts into, spread evenly around it. Traps for trap bombs, bullets otherwise
+    fragments: usize,
+    /// fragments get from 1 to 2 times this speed
+    fragment_speed: f64,
+    /// seconds the fragments live
+    fragment_lifetime: f64,
+    /// radius in which things are pushed away by the burst
+    blast: f64,
+}
+impl Default for Bomb {
+    fn default() -> Self {
+        Self {
+            fragments: 32,
+            fragment_speed: 1152.,
+            fragment_lifetime: 2.,
+            blast: 1024.,
+        }
+    }
+}
+
+/// A turret that hits instantly along a line instead of firing projectiles. It fires after the tank fired it for `charge` seconds, and then waits for the `reload_time` of the turret.
+/// See `Map::fire_lasers`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Laser {
+    /// hp taken from everything the beam hits
+    damage: f64,
+    range: f64,
+    /// how many tanks and shapes the beam goes through. It stops at the last one
+    penetration: usize,
+    /// seconds
+    charge: f64,
+    /// radius of the beam
+    width: f64,
+    /// seconds the beam stays visible
+    beam_time: f64,
+}
+impl Default for Laser {
+    fn default() -> Self {
+        Self {
+            damage: 10.,
+            range: 1500.,
+            penetration: 1,
+            charge: 0.5,
+            width: 6.,
+            beam_time: 0.15,
+        }
+    }
+}
+
+/// What a rocket turns to while it has fuel
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Homing {
+    /// flies straight on
+    Straight,
+    /// the closest enemy tank in `Rocket::homing_range`
+    Enemy,
+    /// where the tank that fired it is aiming
+    Cursor,
+}
+
+/// A projectile with its own engine. It pushes itself where it faces while it has fuel, and can turn towards a target. Afterwards it flies on like a bullet
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Rocket {
+    /// force per second
+    thrust: f64,
+    /// seconds the engine burns
+    fuel: f64,
+    homing: Homing,
+    /// degrees per second the rocket can turn
+    turn_rate: f64,
+    /// how far a rocket homing to enemies sees them
+    homing_range: f64,
+}
+impl Default for Rocket {
+    fn default() -> Self {
+        Self {
+            thrust: 0.,
+            fuel: 1.,
+            homing: Homing::Straight,
+            turn_rate: 90.,
+            homing_range: 1024.,
+        }
+    }
 }
 
 /// Stores `xp`, upgraded levels, and tank class. Has functions for upgrading levels and promoting to higher classes.
@@ -315,7 +627,7 @@ impl Evolution {
     fn new() -> Self {
         Evolution {
             xp: 10000.,
-            class: "basic".to_string(),
+            class: tank_tree::ROOT_CLASS.to_string(),
             hp_level: 0,
             regen_level: 0,
             reload_level: 0,
@@ -346,6 +658,9 @@ impl Evolution {
             ev.class = class;
             ev.xp = old_tank.evolution.xp;
             tank.last_hit_id = old_tank.last_hit_id;
+            tank.team = old_tank.team;
+            // drones and bullets stay with the tank
+            tank.bullet_ids = old_tank.bullet_ids;
             ph.x = old_tank.physics.x;
             ph.y = old_tank.physics.y;
             ph.xvel = old_tank.physics.xvel;
@@ -378,12 +693,14 @@ impl Evolution {
         tank.physics.hp_regen = default_tank.physics.hp_regen;
         tank.power = default_tank.power;
         tank.rot_power = default_tank.rot_power;
+        tank.body_damage = default_tank.body_damage;
         for x in 0..tank.turrets.len() {
             tank.turrets[x].projectile_hp = default_tank.turrets[x].projectile_hp;
             tank.turrets[x].projectile_hp_regen = default_tank.turrets[x].projectile_hp_regen;
             tank.turrets[x].projectile_impulse = default_tank.turrets[x].projectile_impulse;
             tank.turrets[x].projectile_weight = default_tank.turrets[x].projectile_weight;
             tank.turrets[x].reload_time = default_tank.turrets[x].reload_time;
+            tank.turrets[x].laser = default_tank.turrets[x].laser.clone();
         }
 
         for l in 0..tank.evolution.hp_level.min(10) {
@@ -403,7 +720,11 @@ impl Evolution {
     
         // this increases projectile_impulse, projectile_weight, projectile_hp and projectile_hp_regen all by the same coefficient
         for l in 0..tank.evolution.damage_level.min(10) {
+            tank.body_damage *= 1. + 0.08 * (1.6-0.1*l as f64);
             for x in 0..tank.turrets.len() {
+                if let Some(laser) = &mut tank.turrets[x].laser {
+                    laser.damage *= 1. + 0.08 * (1.6-0.1*l as f64);
+                }
                 tank.turrets[x].projectile_hp *= 1. + 0.08 * (1.6-0.1*l as f64);
                 tank.turrets[x].projectile_hp_regen *= 1. + 0.08 * (1.6-0.1*l as f64);
                 tank.turrets[x].projectile_impulse *= 1. + 0.08 * (1.6-0.1*l as f64);
@@ -424,6 +745,25 @@ impl Evolution {
     }
 }
 
+/// What the drones of a tank do, see the drones part of `Map::update_physics`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DroneControl {
+    /// the tank is not firing. Drones go for the closest enemy near the tank, or circle around it
+    Idle,
+    /// drones fly to where the tank is aiming
+    Attract,
+    /// drones fly away from where the tank is aiming
+    Repel,
+}
+
+/// How far from their owner idle drones look for something to attack
+const DRONE_TARGET_RANGE: f64 = 768.;
+
+/// Faded out tanks are seen by everything closer than this, see `Tank::stealth`
+const STEALTH_DETECTION_RADIUS: f64 = 256.;
+/// Tanks moving faster than this count as moving and don't fade out
+const STEALTH_STILL_SPEED: f64 = 20.;
+
 /// A tank. Player, bot, boss etc
 #[derive(Clone, Debug)]
 pub struct Tank {
@@ -440,6 +780,30 @@ pub struct Tank {
     /// contains all the upgrading and evolution related variables and functions
     evolution: Evolution,
     firing_to: (f64, f64),
+    /// 0 for no team. Tanks and bullets of the same team do not damage each other, see `Gamemode::can_damage`
+    team: u8,
+    /// damage taken from every tank, bullets count for the tank that fired them. Used to split the boss reward
+    damage_from: HashMap<u128, f64>,
+    /// how bots should play this class, see `AiHints`
+    ai: AiHints,
+    /// multiplies the damage this tank deals by colliding. Upgraded together with bullet damage
+    body_damage: f64,
+    /// 0 to 1, the part of the push from collisions this tank ignores
+    knockback_resistance: f64,
+    /// how many shots each turret group fired, the turret whose turn it is in a group is this modulo the size of the group. See `Turret::group`
+    group_turns: HashMap<u32, usize>,
+    /// how many drones the tank can have at once, drone turrets wait while it has this many. None for no limit
+    max_drones: Option<usize>,
+    /// drones the tank had in the last update
+    drones: usize,
+    /// what the drones do in this update, set while the tank fires and back to idle after every update
+    drone_control: DroneControl,
+    /// triangles killed by the projectiles of the tank become its drones, like on the infector
+    infects: bool,
+    /// seconds the tank has to stand still without firing to fade out, like the stalker. 0 for tanks that never fade
+    stealth: f64,
+    /// 1 when fully visible, 0 when faded out. Faded out tanks can only be seen from up close, see `Tank::hidden_at`
+    visibility: f64,
 }
 impl Default for Tank {
     /// BASIC tank, might not be updated with latest changed to BASIC
@@ -475,17 +839,34 @@ impl Default for Tank {
             bullet_ids: HashSet::new(),
             evolution: Evolution::new(),
             last_hit_id: 0,
-            firing_to: (0.,0.)
-            
+            firing_to: (0.,0.),
+            team: 0,
+            damage_from: HashMap::new(),
+            ai: AiHints::default(),
+            body_damage: 1.,
+            knockback_resistance: 0.,
+            group_turns: HashMap::new(),
+            max_drones: None,
+            drones: 0,
+            drone_control: DroneControl::Idle,
+            infects: false,
+            stealth: 0.,
+            visibility: 1.,
         }
     }
 }
 impl Tank {
-    fn render(&self, canvas: &mut Canvas<Window>, camera: &Camera, textures: &HashMap<String, Texture>) {
+    fn render(&self, canvas: &mut Canvas<Window>, camera: &Camera, textures: &mut HashMap<String, Texture>) {
         let rendersize = (self.physics.collision_size*8.*camera.zoom*((camera.viewport_size.0.pow(2)+camera.viewport_size.1.pow(2)) as f64).sqrt()/1024.) as u32;
-        let texture = textures.get(&self.texture).expect(&format!{"failed to load texture: {}", &self.texture});
+        let texture = textures.get_mut(&self.texture).expect(&format!{"failed to load texture: {}", &self.texture});
         let tank_screen_pos = camera.to_screen_coords((self.physics.x, self.physics.y));
 
+        // fading tanks are drawn transparent, see `Tank::stealth`
+        let alpha = (255. * self.visibility.max(0.4)) as u8;
+        let fade = |color: Color| Color::RGBA(color.r, color.g, color.b, alpha);
+        texture.set_alpha_mod(alpha);
+        canvas.set_blend_mode(BlendMode::Blend);
+
         canvas.copy_ex(
             &texture, None,
             Rect::from_center(
@@ -495,15 +876,31 @@ impl Tank {
             self.physics.rot, // set rotation
             Point::from((rendersize as i32 / 2, rendersize as i32 / 2)), // set center of rotation, in screen coordinates (not texture coordinates)
             false, false).unwrap();
+        texture.set_alpha_mod(255);
+
+        canvas.filled_circle(tank_screen_pos.0 as i16, tank_screen_pos.1 as i16, (rendersize as f64/8.).ceil() as i16, fade(team_color(self.team))).unwrap();
+
+        // auto turrets are not part of the texture, they turn on their own
+        let scale = rendersize as f64 / (self.physics.collision_size*8.);
+        for turret in self.turrets.iter().filter(|t| t.auto) {
+            let mount = camera.to_screen_coords(turret.mount(&self.physics));
+            let direction = turret.direction(&self.physics).to_radians();
+            let barrel = turret.barrel_length()*scale;
+            let end = (mount.0 + (direction.sin()*barrel) as i32, mount.1 - (direction.cos()*barrel) as i32);
+            let width = (turret.projectile_collision_size*1.6*scale).max(1.);
+            canvas.thick_line(mount.0 as i16, mount.1 as i16, end.0 as i16, end.1 as i16, (width + 2.).min(255.) as u8, fade(Color::BLACK)).unwrap();
+            canvas.thick_line(mount.0 as i16, mount.1 as i16, end.0 as i16, end.1 as i16, width.min(255.) as u8, fade(Color::RGB(63, 63, 63))).unwrap();
+            let radius = (turret.projectile_collision_size*1.2*scale).max(1.) as i16;
+            canvas.filled_circle(mount.0 as i16, mount.1 as i16, radius, fade(Color::RGB(63, 63, 63))).unwrap();
+            canvas.circle(mount.0 as i16, mount.1 as i16, radius, fade(Color::BLACK)).unwrap();
+        }
 
-        canvas.filled_circle(tank_screen_pos.0 as i16, tank_screen_pos.1 as i16, (rendersize as f64/8.).ceil() as i16, Color::RGB(0, 0, 255)).unwrap();
-        
         // render health bar
         if self.physics.hp < self.physics.max_hp {
-            canvas.set_draw_color(Color::RGB(63,15,31));
+            canvas.set_draw_color(fade(Color::RGB(63,15,31)));
             canvas.draw_line((tank_screen_pos.0 - (50 * rendersize / 266) as i32, tank_screen_pos.1 - (60 * rendersize / 266) as i32), (tank_screen_pos.0 + (50 * rendersize / 266) as i32, tank_screen_pos.1 - (60 * rendersize / 266) as i32)).unwrap();
             canvas.draw_line((tank_screen_pos.0 - (50 * rendersize / 266) as i32, tank_screen_pos.1-1 - (60 * rendersize / 266) as i32), (tank_screen_pos.0 + (50 * rendersize / 266) as i32, tank_screen_pos.1-1 - (60 * rendersize / 266) as i32)).unwrap();
-            canvas.set_draw_color(Color::RGB(0,255,0));
+            canvas.set_draw_color(fade(Color::RGB(0,255,0)));
             canvas.draw_line((tank_screen_pos.0 - (50 * rendersize / 266) as i32, tank_screen_pos.1 - (60 * rendersize / 266) as i32), (tank_screen_pos.0 - (50 * rendersize / 266) as i32  + (self.physics.hp/self.physics.max_hp*100. * rendersize as f64 / 266.) as i32, tank_screen_pos.1 - (60 * rendersize / 266) as i32)).unwrap();
             canvas.draw_line((tank_screen_pos.0 - (50 * rendersize / 266) as i32, tank_screen_pos.1-1 - (60 * rendersize / 266) as i32), (tank_screen_pos.0 - (50 * rendersize / 266) as i32  + (self.physics.hp/self.physics.max_hp*100. * rendersize as f64 / 266.) as i32, tank_screen_pos.1-1 - (60 * rendersize / 266) as i32)).unwrap();
         }
@@ -537,18 +934,144 @@ impl Tank {
         self.firing_to = to;
     }
 
-    /// Fires from all the tank's reloaded turrets
+    /// Distance a `TankAI` keeps from its target, about the range of the first turret. 0 for tanks that ram, see `AiHints`
+    fn ai_target_range(&self) -> f64 {
+        if self.ai.rams {
+            0.
+        } else if let Some(range) = self.ai.target_range {
+            range
+        } else if let Some(laser) = self.turrets.first().and_then(|t| t.laser.as_ref()) {
+            laser.range*0.8
+        } else if let Some(t) = self.turrets.first() {
+            (t.projectile_impulse/t.projectile_weight).sqrt()  *  (t.projectile_hp/-t.projectile_hp_regen).sqrt()  *  8.
+        } else {
+            0.
+        }
+    }
+
+    /// Speed of the bullets of the first turret, 0 without turrets. For rockets about their average speed while they burn, and infinite for lasers
+    fn bullet_speed(&self) -> f64 {
+        if self.turrets.first().is_some_and(|t| t.laser.is_some()) {
+            return f64::INFINITY
+        }
+        self.turrets.first().map_or(0., |t| (t.projectile_impulse + t.rocket.as_ref().map_or(0., |r| r.thrust*r.fuel/2.))/t.projectile_weight)
+    }
+
+    /// Some turret of the tank fires drones
+    fn fires_drones(&self) -> bool {
+        self.turrets.iter().any(|t| t.projectile_kind == ProjectileKind::Drone)
+    }
+
+    /// The tank has less drones than its `max_drones`
+    fn has_room_for_drone(&self) -> bool {
+        self.max_drones.is_none_or(|max| self.drones < max)
+    }
+
+    /// The tank is faded out and too far from `from` to be seen
+    fn hidden_at(&self, from: &Physics) -> bool {
+        self.visibility <= 0. && self.physics.dist(from) > STEALTH_DETECTION_RADIUS
+    }
+
+    /// Both tanks are in the same team. Tanks without a team have no teammates
+    fn is_teammate(&self, other: &Tank) -> bool {
+        self.team != 0 && self.team == other.team
+    }
+
+    /// Fires from all the tank's reloaded turrets, besides auto turrets
     /// Will make the bullets belong to `source_id` (for sake of eg. who did the kill)
     fn fire(&mut self, bullets: &mut HashMap<u128, Bullet>, source_id: u128) {
-        for t in &mut self.turrets {
-            let bullet = t.fire(&self.physics, source_id);
-            if bullet.is_some() {
+        // shooting gives away where the tank is
+        self.visibility = 1.;
+        if self.drone_control == DroneControl::Idle {
+            self.drone_control = DroneControl::Attract;
+        }
+        for i in 0..self.turrets.len() {
+            // auto turrets fire on their own, see `Map::aim_auto_turrets`
+            if self.turrets[i].auto {
+                continue
+            }
+            self.turrets[i].pull_trigger();
+            // lasers need the map, see `Map::fire_lasers`
+            if self.turrets[i].laser.is_some() {
+                continue
+            }
+
+            // only the turret whose turn it is fires from a group
+            let group = self.turrets[i].group;
+            let members: Vec<usize> = (0..self.turrets.len()).filter(|j| group != 0 && self.turrets[*j].group == group).collect();
+            if group != 0 && members[self.group_turns.get(&group).copied().unwrap_or(0) % members.len()] != i {
+                continue
+            }
+
+            let is_drone = self.turrets[i].projectile_kind == ProjectileKind::Drone;
+            if is_drone && !self.has_room_for_drone() {
+                continue
+            }
+
+            if let Some(mut bullet) = self.turrets[i].fire(&mut self.physics, source_id) {
+                if is_drone {
+                    self.drones += 1;
+                }
                 let x:u128 = thread_rng().gen();
                 self.bullet_ids.insert(x);
-                bullets.insert(x, bullet.unwrap());
+                bullet.team = self.team;
+                bullets.insert(x, bullet);
+
+                // the next turret of the group waits for the reload of this one
+                if group != 0 {
+                    *self.group_turns.entry(group).or_default() += 1;
+                    let reload = self.turrets[i].time_to_next_shot;
+                    for j in members {
+                        self.turrets[j].time_to_next_shot = reload;
+                    }
+                }
             }
         }
     }
+
+    /// Moves, rotates and fires the tank by the state of the controls. Used for both the local player and network players
+    fn control(&mut self, input: &InputOverNetwork, bullets: &mut HashMap<u128, Bullet>, id: u128, delta: f64) {
+        //movement
+        if input.up_pressed && input.left_pressed && !input.down_pressed && !input.right_pressed {
+            self.move_in_dir((-0.707,-0.707), delta);
+        }
+        else if input.down_pressed && input.left_pressed && !input.up_pressed && !input.right_pressed {
+            self.move_in_dir((-0.707,0.707), delta);
+        }
+        else if input.up_pressed && input.right_pressed && !input.down_pressed && !input.left_pressed {
+            self.move_in_dir((0.707,-0.707), delta);
+        }
+        else if input.down_pressed && input.right_pressed && !input.up_pressed && !input.left_pressed {
+            self.move_in_dir((0.707,0.707), delta);
+        }
+        else if input.up_pressed {
+            self.move_in_dir((0.,-1.), delta);
+        }
+        else if input.down_pressed {
+            self.move_in_dir((0.,1.), delta);
+        }
+        else if input.left_pressed {
+            self.move_in_dir((-1.,0.), delta);
+        }
+        else if input.right_pressed {
+            self.move_in_dir((1.,0.), delta);
+        }
+        else {
+            // brake
+            self.move_in_dir((0.,0.), delta);
+        }
+
+        //rotation
+        self.rotate_to(input.mousepos, delta);
+
+        //firing
+        if input.repel_pressed {
+            self.drone_control = DroneControl::Repel;
+            self.fire(bullets, id);
+        } else if input.fire_pressed {
+            self.fire(bullets, id);
+        }
+    }
 }
 
 /// xy pos, zoom and target tank the camera follows(usally player tank).
@@ -601,7 +1124,13 @@ impl Camera {
 struct Bullet {
     physics: Physics,
     source_tank_id: u128,
+    /// team of the source tank when the bullet was fired, 0 for no team
+    team: u8,
+    /// only cosmetic
     texture: String,
+    kind: ProjectileKind,
+    /// None for projectiles that only get the push from the turret
+    rocket: Option<Rocket>,
 }
 impl Bullet {
     fn render(&self, canvas: &mut Canvas<Window>, camera: &Camera, textures: &HashMap<String, Texture>) {
@@ -619,6 +1148,28 @@ impl Bullet {
     }
 }
 
+/// The visible part of a laser shot, see `Map::fire_lasers`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Beam {
+    from: (f64, f64),
+    to: (f64, f64),
+    width: f64,
+    /// team of the tank that fired it, for the color
+    team: u8,
+    /// seconds until it disappears
+    time_left: f64,
+}
+impl Beam {
+    fn render(&self, canvas: &mut Canvas<Window>, camera: &Camera) {
+        let scale = camera.zoom*((camera.viewport_size.0.pow(2)+camera.viewport_size.1.pow(2)) as f64).sqrt()/1024.;
+        let (from, to) = (camera.to_screen_coords(self.from), camera.to_screen_coords(self.to));
+        let (from, to) = ((from.0.clamp(i16::MIN as i32, i16::MAX as i32) as i16, from.1.clamp(i16::MIN as i32, i16::MAX as i32) as i16), (to.0.clamp(i16::MIN as i32, i16::MAX as i32) as i16, to.1.clamp(i16::MIN as i32, i16::MAX as i32) as i16));
+        let width = (self.width*2.*scale).clamp(1., 255.);
+        canvas.thick_line(from.0, from.1, to.0, to.1, width as u8, team_color(self.team)).unwrap();
+        canvas.thick_line(from.0, from.1, to.0, to.1, (width/3.).max(1.) as u8, Color::WHITE).unwrap();
+    }
+}
+
 /// Tracks info about a button, like if it is pressed, and what keycode or mouse button activates it
 #[derive(Clone, Copy)]
 struct Button {
@@ -647,6 +1198,8 @@ struct Input {
     left: Button,
     right: Button,
     fire: Button,
+    /// drones fly away from the mouse
+    repel: Button,
 
     // u stands for upgrade. these are keys used for upgrading (or promoting when used with shift)
     u1: Button,
@@ -666,6 +1219,17 @@ struct Input {
     zoom_in: Button,
     zoom_out: Button,
 
+    // sandbox tools, see `gamemode::Sandbox`. Next class (previous with shift), spawning at the mouse and toggles
+    class: Button,
+    spawn_square: Button,
+    spawn_triangle: Button,
+    spawn_hexagon: Button,
+    spawn_12gon: Button,
+    spawn_dummy: Button,
+    invulnerable: Button,
+    freeze: Button,
+    bots: Button,
+
     mouse_pos: (i32,i32),
     mouse_delta: (i32,i32)
 }
@@ -678,6 +1242,7 @@ impl Input {
             left: Button { keycode: Some(Keycode::A), mousebutton: None, is_down: false, just: false },
             right: Button { keycode: Some(Keycode::D), mousebutton: None, is_down: false, just: false },
             fire: Button { keycode: None, mousebutton: Some(MouseButton::Left), is_down: false, just: false },
+            repel: Button { keycode: None, mousebutton: Some(MouseButton::Right), is_down: false, just: false },
 
             u1: Button { keycode: Some(Keycode::Num1), mousebutton: None, is_down: false, just: false },
             u2: Button { keycode: Some(Keycode::Num2), mousebutton: None, is_down: false, just: false },
@@ -696,6 +1261,16 @@ impl Input {
             shift: Button { keycode: Some(Keycode::LShift), mousebutton: None, is_down: false, just: false },
             evolve: Button { keycode: Some(Keycode::E), mousebutton: None, is_down: false, just: false },
 
+            class: Button { keycode: Some(Keycode::C), mousebutton: None, is_down: false, just: false },
+            spawn_square: Button { keycode: Some(Keycode::F1), mousebutton: None, is_down: false, just: false },
+            spawn_triangle: Button { keycode: Some(Keycode::F2), mousebutton: None, is_down: false, just: false },
+            spawn_hexagon: Button { keycode: Some(Keycode::F3), mousebutton: None, is_down: false, just: false },
+            spawn_12gon: Button { keycode: Some(Keycode::F4), mousebutton: None, is_down: false, just: false },
+            spawn_dummy: Button { keycode: Some(Keycode::F5), mousebutton: None, is_down: false, just: false },
+            invulnerable: Button { keycode: Some(Keycode::F6), mousebutton: None, is_down: false, just: false },
+            freeze: Button { keycode: Some(Keycode::F7), mousebutton: None, is_down: false, just: false },
+            bots: Button { keycode: Some(Keycode::F8), mousebutton: None, is_down: false, just: false },
+
             mouse_pos: (0,0),
             mouse_delta: (0,0), 
         }
@@ -703,7 +1278,7 @@ impl Input {
 
     /// Finds what this keycode means (up, down, fire, ..) and updates the respective state
     fn register_keydown(&mut self, keycode: Keycode) {
-        for b in [&mut self.up, &mut self.down, &mut self.left, &mut self.right, &mut self.fire, &mut self.u0, &mut self.u1, &mut self.u2, &mut self.u3, &mut self.u4, &mut self.u5, &mut self.u6, &mut self.u7, &mut self.u8, &mut self.u9, &mut self.shift, &mut self.evolve, &mut self.zoom_in, &mut self.zoom_out].iter_mut() {
+        for b in [&mut self.up, &mut self.down, &mut self.left, &mut self.right, &mut self.fire, &mut self.repel, &mut self.u0, &mut self.u1, &mut self.u2, &mut self.u3, &mut self.u4, &mut self.u5, &mut self.u6, &mut self.u7, &mut self.u8, &mut self.u9, &mut self.shift, &mut self.evolve, &mut self.zoom_in, &mut self.zoom_out, &mut self.class, &mut self.spawn_square, &mut self.spawn_triangle, &mut self.spawn_hexagon, &mut self.spawn_12gon, &mut self.spawn_dummy, &mut self.invulnerable, &mut self.freeze, &mut self.bots].iter_mut() {
             if b.keycode.is_some() {
                 if b.keycode.unwrap() == keycode {
                     b.is_down = true;
@@ -715,7 +1290,7 @@ impl Input {
 
     /// Finds what this keycode means (up, down, fire, ..) and updates the respective state
     fn register_keyup(&mut self, keycode: Keycode) {
-        for b in [&mut self.up, &mut self.down, &mut self.left, &mut self.right, &mut self.fire, &mut self.u0, &mut self.u1, &mut self.u2, &mut self.u3, &mut self.u4, &mut self.u5, &mut self.u6, &mut self.u7, &mut self.u8, &mut self.u9, &mut self.shift, &mut self.evolve, &mut self.zoom_in, &mut self.zoom_out].iter_mut() {
+        for b in [&mut self.up, &mut self.down, &mut self.left, &mut self.right, &mut self.fire, &mut self.repel, &mut self.u0, &mut self.u1, &mut self.u2, &mut self.u3, &mut self.u4, &mut self.u5, &mut self.u6, &mut self.u7, &mut self.u8, &mut self.u9, &mut self.shift, &mut self.evolve, &mut self.zoom_in, &mut self.zoom_out, &mut self.class, &mut self.spawn_square, &mut self.spawn_triangle, &mut self.spawn_hexagon, &mut self.spawn_12gon, &mut self.spawn_dummy, &mut self.invulnerable, &mut self.freeze, &mut self.bots].iter_mut() {
             if b.keycode.is_some() {
                 if b.keycode.unwrap() == keycode {
                     b.is_down = false;
@@ -727,7 +1302,7 @@ impl Input {
 
     /// Finds what this mouse button means (up, down, fire, ..) and updates the respective state
     fn register_mouse_button_down(&mut self, mousebutton: MouseButton) {
-        for b in [&mut self.up, &mut self.down, &mut self.left, &mut self.right, &mut self.fire, &mut self.u0, &mut self.u1, &mut self.u2, &mut self.u3, &mut self.u4, &mut self.u5, &mut self.u6, &mut self.u7, &mut self.u8, &mut self.u9, &mut self.shift, &mut self.evolve, &mut self.zoom_in, &mut self.zoom_out].iter_mut() {
+        for b in [&mut self.up, &mut self.down, &mut self.left, &mut self.right, &mut self.fire, &mut self.repel, &mut self.u0, &mut self.u1, &mut self.u2, &mut self.u3, &mut self.u4, &mut self.u5, &mut self.u6, &mut self.u7, &mut self.u8, &mut self.u9, &mut self.shift, &mut self.evolve, &mut self.zoom_in, &mut self.zoom_out, &mut self.class, &mut self.spawn_square, &mut self.spawn_triangle, &mut self.spawn_hexagon, &mut self.spawn_12gon, &mut self.spawn_dummy, &mut self.invulnerable, &mut self.freeze, &mut self.bots].iter_mut() {
             if b.mousebutton.is_some() {
                 if b.mousebutton.unwrap() == mousebutton {
                     b.is_down = true;
@@ -739,7 +1314,7 @@ impl Input {
 
     /// Finds what this mouse button means (up, down, fire, ..) and updates the respective state
     fn register_mouse_button_up(&mut self, mousebutton: MouseButton) {
-        for b in [&mut self.up, &mut self.down, &mut self.left, &mut self.right, &mut self.fire, &mut self.u0, &mut self.u1, &mut self.u2, &mut self.u3, &mut self.u4, &mut self.u5, &mut self.u6, &mut self.u7, &mut self.u8, &mut self.u9, &mut self.shift, &mut self.evolve, &mut self.zoom_in, &mut self.zoom_out].iter_mut() {
+        for b in [&mut self.up, &mut self.down, &mut self.left, &mut self.right, &mut self.fire, &mut self.repel, &mut self.u0, &mut self.u1, &mut self.u2, &mut self.u3, &mut self.u4, &mut self.u5, &mut self.u6, &mut self.u7, &mut self.u8, &mut self.u9, &mut self.shift, &mut self.evolve, &mut self.zoom_in, &mut self.zoom_out, &mut self.class, &mut self.spawn_square, &mut self.spawn_triangle, &mut self.spawn_hexagon, &mut self.spawn_12gon, &mut self.spawn_dummy, &mut self.invulnerable, &mut self.freeze, &mut self.bots].iter_mut() {
             if b.mousebutton.is_some() {
                 if b.mousebutton.unwrap() == mousebutton {
                     b.is_down = false;
@@ -749,9 +1324,26 @@ impl Input {
         }
     }
 
+    /// The state of the movement and fire buttons, in the same form network players send it. Mouse position is converted to map coordinates
+    fn to_network(&self, camera: &Camera) -> InputOverNetwork {
+        InputOverNetwork {
+            up_pressed: self.up.is_down,
+            up_just: self.up.just,
+            down_pressed: self.down.is_down,
+            down_just: self.down.just,
+            left_pressed: self.left.is_down,
+            left_just: self.left.just,
+            right_pressed: self.right.is_down,
+            right_just: self.right.just,
+            fire_pressed: self.fire.is_down,
+            repel_pressed: self.repel.is_down,
+            mousepos: camera.to_map_coords(self.mouse_pos),
+        }
+    }
+
     /// Call this once every loop, before taking input. Now it only changes just to false for all keys
     fn refresh(&mut self) {
-        for b in [&mut self.up, &mut self.down, &mut self.left, &mut self.right, &mut self.fire, &mut self.u0, &mut self.u1, &mut self.u2, &mut self.u3, &mut self.u4, &mut self.u5, &mut self.u6, &mut self.u7, &mut self.u8, &mut self.u9, &mut self.shift, &mut self.evolve, &mut self.zoom_in, &mut self.zoom_out].iter_mut() {
+        for b in [&mut self.up, &mut self.down, &mut self.left, &mut self.right, &mut self.fire, &mut self.repel, &mut self.u0, &mut self.u1, &mut self.u2, &mut self.u3, &mut self.u4, &mut self.u5, &mut self.u6, &mut self.u7, &mut self.u8, &mut self.u9, &mut self.shift, &mut self.evolve, &mut self.zoom_in, &mut self.zoom_out, &mut self.class, &mut self.spawn_square, &mut self.spawn_triangle, &mut self.spawn_hexagon, &mut self.spawn_12gon, &mut self.spawn_dummy, &mut self.invulnerable, &mut self.freeze, &mut self.bots].iter_mut() {
             b.just = false;
         }
     }
@@ -776,13 +1368,30 @@ struct TankAI {
     next_upgrade_is_promotion: bool,
     /// hashmap of tankid, target (only another). When attacking, it will keep the target unless it gets very far or dies. When retreating, it is usually the closest/biggest threat
     tg_id: u128,
+    /// for defensive turrets. The tank never moves or upgrades, and attacks the closest enemy tank without waiting to be hit
+    stationary: bool,
+    /// set by the gamemode, like a control point to capture. The tank goes there when it has no tank to fight
+    objective: Option<(f64, f64)>,
+    /// set by the gamemode, decides when the tank fights and when it flees
+    temper: Temper,
+}
+
+/// How a `TankAI` picks between fight and flight
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Temper {
+    /// fights back when hit, only if it has at least 2x as much hp as the closest enemy. Flees otherwise
+    Normal,
+    /// attacks the closest enemy tank without waiting to be hit, and never flees
+    Hunter,
+    /// flees from the closest enemy tank as soon as it sees it, firing back while running
+    Prey,
 }
 impl TankAI {
     /// Controls all the tanks in it's `tankids` - makes them move and shoot based on `Map`
     ///
     /// Needs to access the whole `Map` mutably to modify the tanks it controls.
     /// 
-    fn control(&mut self, tanks: &mut HashMap<u128, Tank>, shapes: &mut HashMap<u128, Shape>, mut bullets: &mut HashMap<u128, Bullet>, delta: f64) -> bool {
+    fn control(&mut self, tanks: &mut HashMap<u128, Tank>, shapes: &mut HashMap<u128, Shape>, mut bullets: &mut HashMap<u128, Bullet>, walls: &[Wall], delta: f64) -> bool {
         
         let id = self.id;
 
@@ -791,15 +1400,13 @@ impl TankAI {
             let mut con_tank = &mut tanks.get_mut(&id).unwrap();
             let mut movedir = (0.,0.);
 
-            self.tg_range = if con_tank.evolution.class == "shotgun" {
-                128.
-            } else {
-                (con_tank.turrets[0].projectile_impulse/con_tank.turrets[0].projectile_weight).sqrt()  *  (con_tank.turrets[0].projectile_hp/-con_tank.turrets[0].projectile_hp_regen).sqrt()  *  8.
-            };
-            self.bullet_speed = con_tank.turrets[0].projectile_impulse/con_tank.turrets[0].projectile_weight;
+            self.tg_range = con_tank.ai_target_range();
+            self.bullet_speed = con_tank.bullet_speed();
+            // the class can change by evolving
+            self.dodge_obstacles = !con_tank.ai.rams;
             
 
-            if con_tank.evolution.xp > if self.next_upgrade_is_promotion {1000.} else {100.} {
+            if con_tank.evolution.xp > if self.next_upgrade_is_promotion {1000.} else {100.} && !self.stationary {
                 // random bools
                 let mut rb = [false;10];
                 for b in 0..10 {
@@ -874,7 +1481,8 @@ impl TankAI {
             }
 
             // attack the tank that last hit the controlled tank, if it is in range
-            if tanks.contains_key(&tanks.get(&id).unwrap().last_hit_id) && tanks.get(&tanks.get(&id).unwrap().last_hit_id).unwrap().physics.dist(&con_tankp) < self.range && tanks.get(&id).unwrap().last_hit_id != id {
+            // teammates are never attacked, even if they pushed the tank
+            if tanks.contains_key(&tanks.get(&id).unwrap().last_hit_id) && tanks.get(&tanks.get(&id).unwrap().last_hit_id).unwrap().physics.dist(&con_tankp) < self.range && tanks.get(&id).unwrap().last_hit_id != id && !tanks.get(&id).unwrap().is_teammate(tanks.get(&tanks.get(&id).unwrap().last_hit_id).unwrap()) && !tanks.get(&tanks.get(&id).unwrap().last_hit_id).unwrap().hidden_at(&con_tankp) {
                 self.tg_id = tanks.get(&id).unwrap().last_hit_id;
                 tanks.get_mut(&id).unwrap().last_hit_id = 0;
             }
@@ -886,12 +1494,16 @@ impl TankAI {
                 let mut closest_id = 0_u128;
                 let mut closest_dist = self.range;
                 for (oid, tank) in tanks.iter() {
-                    if tank.physics.dist(&tanks.get(&id).unwrap().physics) < closest_dist && id != *oid {
+                    if tank.physics.dist(&tanks.get(&id).unwrap().physics) < closest_dist && id != *oid && !tank.is_teammate(tanks.get(&id).unwrap()) && !tank.hidden_at(&con_tankp) {
                         closest_dist = tank.physics.dist(&tanks.get(&id).unwrap().physics);
                         closest_id = *oid;
                     }
                 }
 
+                if (self.stationary || self.temper != Temper::Normal) && closest_id != 0 {
+                    self.tg_id = closest_id;
+                }
+
                 self.fighting = true;
                 if tanks.contains_key(&closest_id) {
                     let clo_tankp = tanks.get(&closest_id).unwrap().physics;
@@ -900,6 +1512,11 @@ impl TankAI {
                         self.fighting = false
                     }
                 }                
+                match self.temper {
+                    Temper::Hunter => self.fighting = true,
+                    Temper::Prey => self.fighting = false,
+                    Temper::Normal => {}
+                }
             }
 
             // does the target exist in the tanks hashmap
@@ -910,8 +1527,8 @@ impl TankAI {
                 let tgp = tanks.get(&self.tg_id).unwrap().physics;
                 let tg_dist = con_tankp.dist(&tgp);
 
-                // check if target got out of range
-                if tg_dist > self.range {
+                // check if target got out of range, or faded out
+                if tg_dist > self.range || tanks.get(&self.tg_id).unwrap().hidden_at(&con_tankp) {
                     // remove target and continue with next controlled tank
                     self.tg_id = 0;
                     return true
@@ -919,8 +1536,12 @@ impl TankAI {
 
                 let tg_pos = (tgp.x, tgp.y);
                 // not the actual target velocity, but a vector of how much in front of the tank to fire to hit it properly, which depends on tg velocity, distance and bullet speed
-                let tg_vel = if tanks.get_mut(&id).unwrap().texture == "spawner" || tanks.get_mut(&id).unwrap().texture == "infector"  || tanks.get_mut(&id).unwrap().texture == "anthill"  || tanks.get_mut(&id).unwrap().texture == "trapspawner" {
+                let tg_vel = if tanks.get(&id).unwrap().fires_drones() {
                     (0.,0.)
+                } else if tanks.get(&id).unwrap().ai.rams {
+                    // where the target will be when the tank gets there
+                    let time = tg_dist / (con_tankp.speed() + 256.);
+                    ((tgp.xvel - con_tankp.xvel) * time, (tgp.yvel - con_tankp.yvel) * time)
                 } else {
                     ((tgp.xvel - con_tankp.xvel) * ((tg_dist/(0.6 * self.bullet_speed)).exp()) / 5.0, (tgp.yvel - con_tankp.yvel) * ((tg_dist/(0.6 * self.bullet_speed)).exp()) / 5.0)
                 };
@@ -980,6 +1601,11 @@ impl TankAI {
                         movedir = (movedir.0 * 0.01, movedir.1 * 0.01)
                     }
                 }
+
+                // the objective is more important than shapes
+                if let Some(objective) = self.objective {
+                    movedir = normalize((objective.0 - con_tankp.x, objective.1 - con_tankp.y));
+                }
             }
 
             // avoid obstacles
@@ -997,14 +1623,15 @@ impl TankAI {
 
             // avoid bullets
             if self.dodge_obstacles {
+                let con_team = tanks.get(&id).unwrap().team;
                 for (bullet, source) in bullets.iter().map(|s| (s.1, s.1.source_tank_id)) {
-                    // if the bullet is close (distance increases when the tank is going fast)
-                    if con_tankp.dist(&bullet.physics) < (bullet.physics.collision_size + con_tankp.collision_size + bullet.physics.speed()*1. * if bullet.texture == "bomb" || bullet.texture == "mbomb" {1024.} else {1.}) && source != id  {
+                    // if the bullet is close (distance increases when the tank is going fast). Bullets of teammates are harmless
+                    if con_tankp.dist(&bullet.physics) < (bullet.physics.collision_size + con_tankp.collision_size + bullet.physics.speed()*1. * if matches!(bullet.kind, ProjectileKind::Bomb(_) | ProjectileKind::MagnetBomb(_)) {1024.} else {1.}) && source != id && (con_team == 0 || bullet.team != con_team) {
                         let bdist = con_tankp.dist(&bullet.physics);
 
                         // move directly away from the bullet, overriding the move direction determined before
                         let bullet_away_dir = normalize((-(bullet.physics.x + bullet.physics.xvel - con_tankp.x), -(bullet.physics.y + bullet.physics.yvel - con_tankp.y)));
-                        if bullet.texture == "bomb" || bullet.texture == "mbomb" {
+                        if matches!(bullet.kind, ProjectileKind::Bomb(_) | ProjectileKind::MagnetBomb(_)) {
                             movedir = (movedir.0 + bullet_away_dir.0*bullet.physics.hp/con_tankp.hp /bdist * 131072., movedir.1 + bullet_away_dir.1*bullet.physics.hp/con_tankp.hp /bdist * 131072.);
                         } else {
                             movedir = (movedir.0 + bullet_away_dir.0*bullet.physics.hp/con_tankp.hp /bdist * 4., movedir.1 + bullet_away_dir.1*bullet.physics.hp/con_tankp.hp /bdist * 4.);
@@ -1014,7 +1641,24 @@ impl TankAI {
                 }
             }
 
-            tanks.get_mut(&id).unwrap().move_in_dir(movedir, delta);
+            // slide along walls instead of driving into them
+            for wall in walls {
+                let closest = wall.closest_point((con_tankp.x, con_tankp.y));
+                let away = vector_diff(closest, (con_tankp.x, con_tankp.y));
+                if vector_lenght(away) < con_tankp.collision_size + con_tankp.speed()*0.5 + 64. {
+                    let normal = normalize(away);
+                    let into_wall = movedir.0*normal.0 + movedir.1*normal.1;
+                    if into_wall < 0. {
+                        movedir = (movedir.0 - normal.0*into_wall, movedir.1 - normal.1*into_wall);
+                    }
+                    let push = vector_lenght(movedir).max(1.) * 0.5;
+                    movedir = (movedir.0 + normal.0*push, movedir.1 + normal.1*push);
+                }
+            }
+
+            if !self.stationary {
+                tanks.get_mut(&id).unwrap().move_in_dir(movedir, delta);
+            }
 
         } else {
             // Tank with id 'id' is not in 'tanks', it appearently died. Remove from list of controlled tanks
@@ -1039,9 +1683,334 @@ struct Map {
     /// All the things shot by tanks - bullets or drones. Projectiles that make other things (rocket laucher tank, factory tank) aren't supported
     bullets: HashMap<u128, Bullet>, // hashmap to easily iterate over all the tank's bullets, for example when the tank dies, or when there would be a shield that only blocks some tank's bullets (teams?)
     /// a Vec<> of all the different AIs on the map. Each AI controls some tanks, 
-    tankais: Vec<TankAI>
+    tankais: Vec<TankAI>,
+    /// (victim, killer) of the tanks that died this frame, killer is None if no tank killed it. Emptied by the room every frame
+    deaths: Vec<(u128, Option<u128>)>,
+    /// battle royale safe zone, tanks outside of it take damage
+    zone: Option<Zone>,
+    /// the AIs of the bosses on the map, see `boss`
+    bossais: Vec<BossAI>,
+    /// messages for everyone on the map, like a boss spawning. Emptied by the room every frame
+    announcements: Vec<String>,
+    /// static obstacles inside the map, the outer walls are `map_size`
+    walls: Vec<Wall>,
+    /// random by default, gamemodes generate things like mazes from it so a map can be played again
+    seed: u64,
+    /// laser shots that are still visible
+    beams: Vec<Beam>,
 }
 impl Map {
+    fn new(map_size: (f64, f64)) -> Self {
+        Map {
+            map_size,
+            shapes_max: 0,
+            shapes: HashMap::new(),
+            tanks: HashMap::new(),
+            bullets: HashMap::new(),
+            tankais: vec![],
+            deaths: vec![],
+            zone: None,
+            bossais: vec![],
+            announcements: vec![],
+            walls: vec![],
+            seed: thread_rng().gen(),
+            beams: vec![],
+        }
+    }
+
+    /// Adds a tank of a class at a random position on the map, and refreshes its levels
+    fn spawn_tank(&mut self, id: u128, class: &str) {
+        self.tanks.insert(
+            id,
+            EVOLUTION_TREE.get(&class.to_owned()).unwrap().0.clone()
+        );
+
+        let ph = &mut self.tanks.get_mut(&id).unwrap().physics;
+        ph.x = thread_rng().gen::<f64>()*self.map_size.0*2. - self.map_size.0;
+        ph.y = thread_rng().gen::<f64>()*self.map_size.1*2. - self.map_size.1;
+        // will be clamped to max hp automatically
+        // ph.hp = 10000.;
+
+        // let ev = &mut self.tanks.get_mut(&id).unwrap().evolution;
+        // ev.hp_level = thread_rng().gen_range(10..11);
+        // ev.regen_level = thread_rng().gen_range(10..11);
+        // ev.reload_level = thread_rng().gen_range(10..11);
+        // ev.damage_level = thread_rng().gen_range(10..11);
+        // ev.speed_level = thread_rng().gen_range(10..11);
+        // ev.bulletspeed_level = thread_rng().gen_range(10..11);
+
+        Evolution::level_refresh(self.tanks.get_mut(&id).unwrap());
+    }
+
+    /// Adds AI controlled tanks until there are `count` tanks on the map. Returns the ids of the new tanks
+    fn spawn_bots(&mut self, count: usize) -> Vec<u128> {
+        let mut spawned = vec![];
+        while self.tanks.len() < count {
+            spawned.push(self.spawn_bot(tank_tree::ROOT_CLASS));
+        }
+        spawned
+    }
+
+    /// Adds one AI controlled tank of a class at a random position. Returns its id
+    fn spawn_bot(&mut self, class: &str) -> u128 {
+        let ai_tank_id = thread_rng().gen::<u128>();
+
+        // add AI tank
+        // tanks will be network or AI controlled on the server (also player controlled on LAN multiplayer server), and player or AI controlled in singleplayer
+        self.tanks.insert(
+            ai_tank_id,
+            EVOLUTION_TREE.get(&class.to_owned()).unwrap().0.clone()
+        );
+        let ph = &mut self.tanks.get_mut(&ai_tank_id).unwrap().physics;
+        ph.x = thread_rng().gen::<f64>()*self.map_size.0*2. - self.map_size.0;
+        ph.y = thread_rng().gen::<f64>()*self.map_size.1*2. - self.map_size.1;
+        // will be clamped to max hp automatically
+        ph.hp = 10000.;
+
+        let ev = &mut self.tanks.get_mut(&ai_tank_id).unwrap().evolution;
+        ev.hp_level = thread_rng().gen_range(0..1);
+        ev.regen_level = thread_rng().gen_range(0..1);
+        ev.reload_level = thread_rng().gen_range(0..1);
+        ev.damage_level = thread_rng().gen_range(0..1);
+        ev.speed_level = thread_rng().gen_range(0..1);
+        ev.bulletspeed_level = thread_rng().gen_range(0..1);
+        ev.class = class.to_owned();
+
+        let tank =&mut self.tanks.get_mut(&ai_tank_id).unwrap();
+        Evolution::level_refresh(tank);
+
+        self.tankais.push(TankAI {
+            id: ai_tank_id,
+            range: 3072.,
+            tg_range: tank.ai_target_range(),
+            bullet_speed: tank.bullet_speed(),
+            dodge_obstacles: !tank.ai.rams,
+            fighting: true,
+            tg_id: 0,
+            next_upgrade_is_promotion: false,
+            stationary: false,
+            objective: None,
+            temper: Temper::Normal,
+        });
+        ai_tank_id
+    }
+
+    /// Adds a boss at a random position and announces it. Returns its id
+    fn spawn_boss(&mut self) -> u128 {
+        let id = thread_rng().gen::<u128>();
+        let mut boss = boss::boss_tank();
+        boss.physics.x = thread_rng().gen::<f64>()*self.map_size.0*2. - self.map_size.0;
+        boss.physics.y = thread_rng().gen::<f64>()*self.map_size.1*2. - self.map_size.1;
+        self.announcements.push(format!("A boss appeared at {:.0}, {:.0}", boss.physics.x, boss.physics.y));
+        self.tanks.insert(id, boss);
+        self.bossais.push(BossAI::new(id));
+        id
+    }
+
+    /// Charges the lasers of the tanks that are firing them, and fires the charged ones. A beam instantly hits the first `Laser::penetration` tanks and shapes on its way, walls stop it
+    fn fire_lasers(&mut self, gamemode: &dyn Gamemode, delta: f64) {
+        self.beams.retain_mut(|beam| {
+            beam.time_left -= delta;
+            beam.time_left > 0.
+        });
+
+        let mut shots = vec![];
+        for (id, tank) in self.tanks.iter_mut() {
+            for turret in tank.turrets.iter_mut() {
+                let laser = match &turret.laser {
+                    Some(laser) => laser.clone(),
+                    None => continue,
+                };
+                // letting go of fire loses the charge
+                if !turret.triggered {
+                    turret.charged = 0.;
+                    continue
+                }
+                if turret.time_to_next_shot > 0. {
+                    continue
+                }
+                turret.charged += delta;
+                if turret.charged >= laser.charge {
+                    turret.charged = 0.;
+                    turret.time_to_next_shot = turret.reload_time;
+                    shots.push((*id, tank.team, turret.mount(&tank.physics), turret.direction(&tank.physics), laser));
+                }
+            }
+        }
+
+        for (id, team, from, direction, laser) in shots {
+            let dir = (direction.to_radians().sin(), -direction.to_radians().cos());
+            let range = self.walls.iter().filter_map(|wall| wall.raycast(from, dir)).fold(laser.range, f64::min);
+
+            // (distance along the beam, id) of everything the beam touches, the closest first
+            let mut hits = vec![];
+            let tanks = self.tanks.iter().filter(|(oid, _)| **oid != id).map(|(oid, tank)| (oid, &tank.physics));
+            let shapes = self.shapes.iter().map(|(oid, shape)| (oid, &shape.physics));
+            for (oid, op) in tanks.chain(shapes) {
+                let to = (op.x - from.0, op.y - from.1);
+                let along = to.0*dir.0 + to.1*dir.1;
+                let across = (to.0*dir.1 - to.1*dir.0).abs();
+                // where the beam enters the target, nothing behind a wall is hit
+                let entry = along - ((op.collision_size + laser.width).powi(2) - across.powi(2)).max(0.).sqrt();
+                if along > -op.collision_size && entry < range && across < op.collision_size + laser.width && gamemode.can_damage(self, id, *oid) {
+                    hits.push((along.max(0.), *oid));
+                }
+            }
+            hits.sort_by(|a, b| a.0.total_cmp(&b.0));
+            hits.truncate(laser.penetration);
+            let length = if hits.len() == laser.penetration {
+                hits.last().map_or(range, |(along, _)| along.min(range))
+            } else {
+                range
+            };
+
+            for (_, oid) in hits {
+                if let Some(tank) = self.tanks.get_mut(&oid) {
+                    let was_alive = tank.physics.hp >= 0.;
+                    let damage = laser.damage.min(tank.physics.hp.max(0.));
+                    tank.physics.hp -= laser.damage;
+                    tank.last_hit_id = id;
+                    let killvalue = tank.evolution.killvalue;
+                    let killed = was_alive && tank.physics.hp < 0.;
+                    self.record_damage(oid, id, damage);
+                    // add xp for kill, if the tank that killed is alive
+                    if killed {
+                        if let Some(shooter) = self.tanks.get_mut(&id) {
+                            shooter.evolution.xp += killvalue;
+                        }
+                    }
+                } else if let Some(shape) = self.shapes.get_mut(&oid) {
+                    let was_alive = shape.physics.hp > 0.;
+                    shape.physics.hp -= laser.damage;
+                    if was_alive && shape.physics.hp <= 0. && !shape.just_spawned_mode {
+                        let xp = shape.physics.collision_size.powi(2)*0.01;
+                        if let Some(tank) = self.tanks.get_mut(&id) {
+                            tank.evolution.add_xp(xp);
+                        }
+                    }
+                }
+            }
+
+            self.beams.push(Beam {
+                from,
+                to: (from.0 + dir.0*length, from.1 + dir.1*length),
+                width: laser.width,
+                team,
+                time_left: laser.beam_time,
+            });
+        }
+    }
+
+    /// Turns every auto turret to the closest enemy tank in its range and arc, or the closest shape if there is no tank, and fires it.
+    /// Aims in front of moving targets. Auto turrets without a target stop turning and don't fire
+    fn aim_auto_turrets(&mut self, gamemode: &dyn Gamemode) {
+        let mut aims = vec![];
+        for (id, tank) in self.tanks.iter() {
+            for (t, turret) in tank.turrets.iter().enumerate().filter(|(_, t)| t.auto) {
+                let mount = turret.mount(&tank.physics);
+                let rest = turret.relative_direction + tank.physics.rot;
+                let speed = turret.projectile_impulse/turret.projectile_weight;
+
+                // the turn from the rest direction to the target
+                let turn = self.closest_target(gamemode, *id, mount, turret.auto_range, |op, dist| {
+                    // bullets keep the velocity of the tank, so lead by the velocity relative to it
+                    let time = dist / speed.max(1.);
+                    let to = (op.x + (op.xvel - tank.physics.xvel)*time, op.y + (op.yvel - tank.physics.yvel)*time);
+                    let angle = f64::atan2(to.0 - mount.0, -(to.1 - mount.1)).to_degrees();
+                    let mut turn = (angle - rest).rem_euclid(360.);
+                    if turn > 180. {
+                        turn -= 360.;
+                    }
+                    (turn.abs() <= turret.auto_arc).then_some(turn)
+                });
+                aims.push((*id, t, turn));
+            }
+        }
+
+        for (id, t, turn) in aims {
+            let tank = self.tanks.get_mut(&id).unwrap();
+            let turret = &mut tank.turrets[t];
+            turret.has_target = turn.is_some();
+            if let Some(turn) = turn {
+                turret.aim = turn;
+                turret.pull_trigger();
+                if let Some(mut bullet) = turret.fire(&mut tank.physics, id) {
+                    tank.visibility = 1.;
+                    let x: u128 = thread_rng().gen();
+                    tank.bullet_ids.insert(x);
+                    bullet.team = tank.team;
+                    self.bullets.insert(x, bullet);
+                }
+            }
+        }
+    }
+
+    /// What the tank `id` should attack within `range` of `from`: the closest enemy tank, or the closest shape if there is none.
+    /// Teammates, faded out tanks and what the gamemode doesn't let the tank damage are left out.
+    ///
+    /// `aim` is called with the physics and distance of a target closer than the best one so far, it can skip it by returning None. Returns the result of `aim` for the chosen target
+    fn closest_target<T>(&self, gamemode: &dyn Gamemode, id: u128, from: (f64, f64), range: f64, mut aim: impl FnMut(&Physics, f64) -> Option<T>) -> Option<T> {
+        let tank = &self.tanks[&id];
+        let tanks = self.tanks.iter().filter(|(oid, other)| **oid != id && !tank.is_teammate(other) && !other.hidden_at(&tank.physics)).map(|(oid, other)| (true, oid, &other.physics));
+        let shapes = self.shapes.iter().map(|(oid, shape)| (false, oid, &shape.physics));
+        // (is a tank, distance, result of aim). Tanks go before shapes, then the closest
+        let mut best: Option<(bool, f64, T)> = None;
+        for (is_tank, oid, op) in tanks.chain(shapes) {
+            let dist = vector_lenght(vector_diff(from, (op.x, op.y)));
+            if dist > range + op.collision_size || !gamemode.can_damage(self, id, *oid) {
+                continue
+            }
+            if best.as_ref().is_none_or(|(best_is_tank, best_dist, _)| (is_tank, -dist) > (*best_is_tank, -*best_dist)) {
+                if let Some(result) = aim(op, dist) {
+                    best = Some((is_tank, dist, result));
+                }
+            }
+        }
+        best.map(|(_, _, result)| result)
+    }
+
+    /// Adds `damage` dealt by the object `by` to the damage taken by the tank `victim`. Damage by bullets counts for the tank that fired them
+    fn record_damage(&mut self, victim: u128, by: u128, damage: f64) {
+        if damage <= 0. {
+            return
+        }
+        let attacker = if let Some(bullet) = self.bullets.get(&by) {
+            bullet.source_tank_id
+        } else if self.tanks.contains_key(&by) {
+            by
+        } else {
+            return
+        };
+        if let Some(tank) = self.tanks.get_mut(&victim) {
+            *tank.damage_from.entry(attacker).or_default() += damage;
+        }
+    }
+
+    /// (`Tank::body_damage`, `Tank::knockback_resistance`) of a tank, shapes and bullets deal normal damage and have no resistance
+    fn body_stats(&self, id: u128) -> (f64, f64) {
+        match self.tanks.get(&id) {
+            Some(tank) => (tank.body_damage, tank.knockback_resistance),
+            None => (1., 0.),
+        }
+    }
+
+    /// Static walls block everything. Tanks, shapes, drones and traps slide along them, other bullets die
+    fn collide_walls(&mut self) {
+        for o in self.tanks.values_mut().map(|t| &mut t.physics).chain(self.shapes.values_mut().map(|s| &mut s.physics)) {
+            for wall in &self.walls {
+                wall.block(o);
+            }
+        }
+        for bullet in self.bullets.values_mut() {
+            let slides = bullet.kind.slides_on_walls();
+            for wall in &self.walls {
+                if wall.block(&mut bullet.physics) && !slides {
+                    bullet.physics.hp = 0.;
+                }
+            }
+        }
+    }
+
     /// renders grid, walls, maybe more in the future
     fn render(&self, canvas: &mut Canvas<Window> , camera: &Camera) {
         for x in ((camera.x - 1./camera.zoom*camera.viewport_size.0 as f64).floor() as i32..(camera.x + 1./camera.zoom*camera.viewport_size.0 as f64).ceil() as i32).filter(|x| x%100 == 0) {
@@ -1053,6 +2022,87 @@ impl Map {
             canvas.set_draw_color(Color::GRAY);
             canvas.draw_line(Point::from(camera.to_screen_coords((self.map_size.1, y as f64))), Point::from(camera.to_screen_coords((-self.map_size.1, y as f64)))).expect("failed to draw line");
         }
+
+        for wall in &self.walls {
+            let (min, max) = wall.bounds();
+            let size = vector_lenght(vector_diff(min, max));
+            if !camera.visible(((min.0 + max.0)/2., (min.1 + max.1)/2.), size/8.) {
+                continue
+            }
+            // the gfx functions take i16, walls partly off screen are clamped
+            let (vx, vy): (Vec<i16>, Vec<i16>) = wall.points.iter().map(|p| {
+                let (x, y) = camera.to_screen_coords(*p);
+                (x.clamp(i16::MIN as i32, i16::MAX as i32) as i16, y.clamp(i16::MIN as i32, i16::MAX as i32) as i16)
+            }).unzip();
+            canvas.filled_polygon(&vx, &vy, Color::RGB(72, 72, 72)).unwrap();
+            canvas.polygon(&vx, &vy, Color::RGB(160, 160, 160)).unwrap();
+        }
+
+        if let Some(zone) = &self.zone {
+            let zoom = camera.zoom * ((camera.viewport_size.0.pow(2) + camera.viewport_size.1.pow(2)) as f64).sqrt() / 1024.;
+            draw_circle_outline(canvas, camera.to_screen_coords(zone.center), zone.radius*zoom, Color::RGB(255, 0, 0));
+            if let Some((center, radius)) = zone.next() {
+                draw_circle_outline(canvas, camera.to_screen_coords(center), radius*zoom, Color::WHITE);
+            }
+        }
+    }
+
+    /// Small map in the bottom right corner, shows the map borders, walls, the zone and the tank of the player
+    fn render_minimap(&self, canvas: &mut Canvas<Window>, camera: &Camera, player_id: u128) {
+        let size = 256;
+        let corner = (camera.viewport_size.0 - size - 40, camera.viewport_size.1 - size - 40);
+        // the minimap always shows the whole map, even when it got smaller
+        let scale = size as f64 / (self.map_size.0.max(self.map_size.1) * 2.);
+        let to_minimap = |(x, y): (f64, f64)| (corner.0 + size/2 + (x*scale) as i32, corner.1 + size/2 + (y*scale) as i32);
+
+        canvas.set_draw_color(Color::RGB(32, 32, 32));
+        canvas.fill_rect(Rect::new(corner.0, corner.1, size as u32, size as u32)).unwrap();
+        canvas.set_draw_color(Color::GRAY);
+        canvas.draw_rect(Rect::new(corner.0, corner.1, size as u32, size as u32)).unwrap();
+
+        canvas.set_draw_color(Color::RGB(160, 160, 160));
+        for wall in &self.walls {
+            let mut points: Vec<Point> = wall.points.iter().map(|p| Point::from(to_minimap(*p))).collect();
+            points.push(points[0]);
+            canvas.draw_lines(points.as_slice()).unwrap();
+        }
+
+        if let Some(zone) = &self.zone {
+            draw_circle_outline(canvas, to_minimap(zone.center), zone.radius*scale, Color::RGB(255, 0, 0));
+            if let Some((center, radius)) = zone.next() {
+                draw_circle_outline(canvas, to_minimap(center), radius*scale, Color::WHITE);
+            }
+        }
+
+        if let Some(player) = self.tanks.get(&player_id) {
+            let (x, y) = to_minimap((player.physics.x, player.physics.y));
+            canvas.filled_circle(x as i16, y as i16, 3, team_color(player.team)).unwrap();
+        }
+    }
+
+    /// Moves the zone along its schedule and damages the tanks outside of it. Dead tanks are recorded by `update_physics`
+    fn update_zone(&mut self, delta: f64) {
+        let Some(zone) = &mut self.zone else {
+            return
+        };
+        zone.update(delta);
+        let damage = zone.damage() * delta;
+        for tank in self.tanks.values_mut() {
+            if !zone.contains((tank.physics.x, tank.physics.y)) {
+                tank.physics.hp -= damage;
+            }
+        }
+    }
+
+    /// Team of a tank or bullet, 0 for shapes, objects without a team and objects that do not exist
+    fn team_of(&self, k: &u128) -> u8 {
+        if let Some(tank) = self.tanks.get(k) {
+            tank.team
+        } else if let Some(bullet) = self.bullets.get(k) {
+            bullet.team
+        } else {
+            0
+        }
     }
 
     /// Finds the physics by u128 key, searches in tanks, bullets and shapes.
@@ -1087,8 +2137,13 @@ impl Map {
     /// 
     /// Randomly spawns shapes
     /// 
-    fn update_physics(&mut self, delta: f64) {
+    /// Asks `gamemode` which collisions deal damage, and records dead tanks in `deaths`
+    fn update_physics(&mut self, delta: f64, gamemode: &dyn Gamemode) {
 
+        // tanks that glitched out die too
+        for (id, _) in self.tanks.iter().filter(|(_, v)| v.physics.speed() > 50_000.) {
+            self.deaths.push((*id, None));
+        }
         self.tanks.retain(|_, v| v.physics.speed() <= 50_000.);
         self.shapes.retain(|_, v| v.physics.speed() <= 50_000.);
 
@@ -1098,75 +2153,27 @@ impl Map {
             let (x, y) = (thread_rng().gen_range(-self.map_size.0..self.map_size.0), thread_rng().gen_range(-self.map_size.0..self.map_size.0));
 
             // from 0.8 to 1.2, squared 0.64 to 1.44
-            let mut size = thread_rng().gen::<f64>() * 0.4 + 0.8;
+            let size = thread_rng().gen::<f64>() * 0.4 + 0.8;
             let mut is_hexagon = thread_rng().gen_bool(0.1);
             let is_triangle = thread_rng().gen_bool(0.5);
             let mut is_12gon = false;
 
             if is_hexagon || ((self.shapes_max - self.shapes.len()) > (self.shapes_max as f64 * 0.1) as usize) {
                 if self.shapes.iter().filter(|i| i.1.texture == "12gon").count() < (self.shapes_max as f64 * 0.01) as usize {
-                    size *= 3.;
                     is_12gon = true;
                     is_hexagon = true;
                 }
             }
-
-            if is_hexagon {
-                size *= 4.;
-            }
-
-            if is_triangle && !is_hexagon {
-                size *= 1.2;
-            }
-            let shape_id = thread_rng().gen::<u128>();
-            self.shapes.insert(shape_id, Shape {
-                physics: Physics {
-                    x,
-                    y,
-                    xvel: 0.,
-                    yvel: 0.,
-                    weight: 1.,
-                    rot: thread_rng().gen::<f64>()*360.,
-                    rotvel: 0.,
-                    collision_size: 20. * size,
-                    hp: 4.,
-                    max_hp: if is_hexagon {
-                        if is_12gon {
-                            300. * size.powi(2)
-                        } else {
-                            30. * size.powi(2)
-                        }
-                    } else if is_triangle{
-                        2.0 * size.powi(2)
-                    } else {
-                        10. * size.powi(2)
-                    },
-                    // hp regen is multiplied by 64, because it is later divided by 64 when shape reaches full hp.
-                    hp_regen: 16. *
-                    if is_hexagon {
-                        if is_12gon {
-                            1. * size.powi(2)
-                        } else {
-                            0.15 * size.powi(2)
-                        }
-                    } else if is_triangle{
-                        3. * size.powi(2)
-                    } else {
-                        0.5 * size.powi(2)
-                    },
-                },
-                texture: if is_12gon {
-                    "12gon".to_owned()
-                }
-                else if is_hexagon {
-                    "hexagon".to_owned()
-                } else if is_triangle{
-                    "triangle".to_owned()
-                } else {
-                    "square".to_owned()
-                },
-                just_spawned_mode: true,
-            });
+            let kind = if is_12gon {
+                "12gon"
+            } else if is_hexagon {
+                "hexagon"
+            } else if is_triangle {
+                "triangle"
+            } else {
+                "square"
+            };
+            self.shapes.insert(thread_rng().gen::<u128>(), Shape::new(kind, size, (x, y)));
         }
 
 
@@ -1198,17 +2205,44 @@ impl Map {
                 o.update(delta);
             }
 
+            self.collide_walls();
+
             // traps slow down 4x faster
             for (id, b) in self.bullets.iter_mut() {
-                if b.texture == "trap" || b.texture == "trapbomb" {
+                if b.kind.slows_down() {
                     b.physics.xvel *= (-delta * 1.5).exp();
                     b.physics.yvel *= (-delta * 1.5).exp();
                 }
             }
 
-            // remove <0 hp tanks
+            // remove <0 hp tanks, the tank that hit them last killed them
+            let mut dead_bosses = vec![];
+            for (id, tank) in self.tanks.iter().filter(|(_, v)| v.physics.hp < 0.) {
+                let killer = if tank.last_hit_id != 0 && tank.last_hit_id != *id && self.tanks.contains_key(&tank.last_hit_id) {
+                    Some(tank.last_hit_id)
+                } else {
+                    None
+                };
+                self.deaths.push((*id, killer));
+                if self.bossais.iter().any(|ai| ai.id == *id) {
+                    dead_bosses.push(tank.damage_from.clone());
+                }
+            }
             self.tanks.retain(|_, v| v.physics.hp >= 0.);
 
+            // the boss reward is split by damage, between the tanks that are still alive
+            for damage_from in dead_bosses {
+                let total: f64 = damage_from.iter().filter(|(id, _)| self.tanks.contains_key(id)).map(|(_, d)| d).sum();
+                let mut contributors = 0;
+                for (id, damage) in damage_from.iter() {
+                    if let Some(tank) = self.tanks.get_mut(id) {
+                        tank.evolution.xp += boss::BOSS_XP * damage / total;
+                        contributors += 1;
+                    }
+                }
+                self.announcements.push(format!("The boss was defeated, {} tanks share {} xp", contributors, boss::BOSS_XP));
+            }
+
             // for shapes and bullets, hexagons and bombs must be removed differently
 
             // find hexes that died
@@ -1243,7 +2277,7 @@ impl Map {
             // find bullets that died, also remove bullet ids from tank source
             let mut bombs_to_remove = Vec::new();
             for (id, bullet) in self.bullets.iter() {
-                if (bullet.texture == "bomb"  || bullet.texture == "mbomb"  || bullet.texture == "trapbomb") && bullet.physics.hp <= 0. {
+                if bullet.kind.bomb().is_some() && bullet.physics.hp <= 0. {
                     bombs_to_remove.push(id.clone());
                 }
             }
@@ -1251,20 +2285,27 @@ impl Map {
             for id in bombs_to_remove {
                 // handle dead bombs here
                 let bomb = &mut self.bullets.get(&id).unwrap().clone();
+                let burst = *bomb.kind.bomb().unwrap();
                 let combined_iter_mut = self.tanks.iter_mut().filter(|t| *t.0 != id).map(|tank| &mut tank.1.physics)
                 .chain(self.shapes.iter_mut().map(|shape| &mut shape.1.physics))
                 .chain(self.bullets.iter_mut().map(|bullet| &mut bullet.1.physics));
                 for o in combined_iter_mut {
-                    if o.dist(&bomb.physics) < bomb.physics.collision_size.powi(2) {
-                        let s = (o.dist(&bomb.physics) - bomb.physics.collision_size.powi(2))*64.;
+                    if o.dist(&bomb.physics) < burst.blast {
+                        let s = (o.dist(&bomb.physics) - burst.blast)*64.;
                         let dir = normalize(vector_diff((o.x, o.y), (bomb.physics.x, bomb.physics.y)));
                         o.push((s*dir.0, s*dir.1));
                     }
                 }
 
                 if bomb.physics.hp <= 0. {
-                    for x in 0..(bomb.physics.collision_size as i32) {
-                        let angle = (x*(360/bomb.physics.collision_size as i32)) as f64;
+                    // the fragments of trap bombs are traps
+                    let (kind, texture) = if let ProjectileKind::TrapBomb(_) = bomb.kind {
+                        (ProjectileKind::Trap, "trap")
+                    } else {
+                        (ProjectileKind::Bullet, "bullet")
+                    };
+                    for x in 0..burst.fragments {
+                        let angle = x as f64 * 360. / burst.fragments as f64;
     
                         let bomb = &mut self.bullets.get(&id).unwrap().clone();
                         let size = 16.;
@@ -1272,17 +2313,20 @@ impl Map {
                         bomb.physics.weight = bomb.physics.weight;
                         bomb.physics.max_hp = bomb.physics.max_hp;
                         bomb.physics.hp = bomb.physics.max_hp;
-                        bomb.physics.hp_regen = if bomb.texture != "trapbomb" {-bomb.physics.max_hp/2.} else {-bomb.physics.max_hp/20.};
-                        bomb.physics.xvel += angle.to_radians().sin() * size * if bomb.texture != "trapbomb" {72.} else {16.} * (1. + thread_rng().gen::<f64>());
-                        bomb.physics.yvel += angle.to_radians().cos() * size * if bomb.texture != "trapbomb" {72.} else {16.} * (1. + thread_rng().gen::<f64>());
+                        bomb.physics.hp_regen = -bomb.physics.max_hp/burst.fragment_lifetime;
+                        bomb.physics.xvel += angle.to_radians().sin() * burst.fragment_speed * (1. + thread_rng().gen::<f64>());
+                        bomb.physics.yvel += angle.to_radians().cos() * burst.fragment_speed * (1. + thread_rng().gen::<f64>());
                         bomb.physics.x += angle.to_radians().sin() * size * 2. * thread_rng().gen::<f64>();
                         bomb.physics.y += angle.to_radians().cos() * size * 2. * thread_rng().gen::<f64>();
 
                         let id = thread_rng().gen();
                         self.bullets.insert(id, Bullet {
                             physics: bomb.physics,
-                            texture: if bomb.texture != "trapbomb" {"bullet".to_owned()} else {"trap".to_owned()},
+                            texture: texture.to_owned(),
                             source_tank_id: bomb.source_tank_id,
+                            team: bomb.team,
+                            kind,
+                            rocket: None,
                         });
                         if self.tanks.contains_key(&bomb.source_tank_id) {
                             self.tanks.get_mut(&bomb.source_tank_id).unwrap().bullet_ids.insert(id);
@@ -1301,32 +2345,107 @@ impl Map {
 
             // remove all dead shapes now
             self.shapes.retain(|_, v| v.physics.hp > 0.);
+            // drones die with their owner, also when it respawned with the same id
+            for (id, d) in self.bullets.iter_mut().filter(|(_, d)| d.kind == ProjectileKind::Drone) {
+                if !self.tanks.get(&d.source_tank_id).is_some_and(|t| t.bullet_ids.contains(id)) {
+                    d.physics.hp = 0.;
+                }
+            }
             // remove all dead bullets now
             self.bullets.retain(|_, v| v.physics.hp > 0.);
 
 
+            // ROCKETS
+
+            // rockets that still have fuel turn to their target
+            let mut rocket_targets = vec![];
+            for (id, b) in self.bullets.iter() {
+                let rocket = match &b.rocket {
+                    Some(rocket) if rocket.fuel > 0. => rocket,
+                    _ => continue,
+                };
+                let target = match rocket.homing {
+                    Homing::Straight => None,
+                    Homing::Cursor => self.tanks.get(&b.source_tank_id).map(|t| t.firing_to),
+                    Homing::Enemy => self.tanks.iter()
+                        .filter(|(tid, t)| **tid != b.source_tank_id && (b.team == 0 || t.team != b.team) && !t.hidden_at(&b.physics) && gamemode.can_damage(self, *id, **tid))
+                        .map(|(_, t)| (b.physics.dist(&t.physics), (t.physics.x, t.physics.y)))
+                        .filter(|(dist, _)| *dist < rocket.homing_range)
+                        .min_by(|a, b| a.0.total_cmp(&b.0))
+                        .map(|(_, to)| to),
+                };
+                rocket_targets.push((*id, target));
+            }
+            for (id, target) in rocket_targets {
+                let b = self.bullets.get_mut(&id).unwrap();
+                let rocket = b.rocket.as_mut().unwrap();
+                if let Some(to) = target {
+                    let angle = f64::atan2(to.0 - b.physics.x, -(to.1 - b.physics.y)).to_degrees();
+                    let mut turn = (angle - b.physics.rot).rem_euclid(360.);
+                    if turn > 180. {
+                        turn -= 360.;
+                    }
+                    b.physics.rot += turn.clamp(-rocket.turn_rate*delta, rocket.turn_rate*delta);
+                }
+                // push forward, and lose most of the sideways speed like on fins
+                let facing = (b.physics.rot.to_radians().sin(), -b.physics.rot.to_radians().cos());
+                let forward = b.physics.xvel*facing.0 + b.physics.yvel*facing.1;
+                let side = (b.physics.xvel - facing.0*forward, b.physics.yvel - facing.1*forward);
+                b.physics.xvel -= side.0*(1. - (-delta*4.).exp());
+                b.physics.yvel -= side.1*(1. - (-delta*4.).exp());
+                b.physics.push((facing.0*rocket.thrust*delta, facing.1*rocket.thrust*delta));
+                rocket.fuel -= delta;
+            }
+
             // DRONES
 
-            // for tank that makes drones
-            for (id, t) in self.tanks.iter().filter(|t| t.1.texture == "spawner" || t.1.texture == "infector" || t.1.texture == "anthill" || t.1.texture == "trapspawner")  {
-                // for drone in tank's bulletids
+            // idle drones go for the closest enemy tank near their owner, or the closest shape if there is none
+            let mut drone_targets = HashMap::new();
+            for (id, t) in self.tanks.iter().filter(|(_, t)| t.drone_control == DroneControl::Idle && t.drones > 0) {
+                if let Some(to) = self.closest_target(gamemode, *id, (t.physics.x, t.physics.y), DRONE_TARGET_RANGE, |op, _| Some((op.x, op.y))) {
+                    drone_targets.insert(*id, to);
+                }
+            }
+
+            for (id, t) in self.tanks.iter_mut() {
+                t.drones = 0;
                 for d_id in t.bullet_ids.iter() {
-                    // move drone in direction to tank's firing_to
-                    let d = &mut self.bullets.get_mut(&d_id).unwrap();
-                    if d.texture == "drone" {
-                        let vdiff = vector_diff((d.physics.x, d.physics.y), t.firing_to);
-                        let dir;
+                    let d = match self.bullets.get_mut(d_id) {
+                        Some(d) if d.kind == ProjectileKind::Drone => d,
+                        _ => continue,
+                    };
+                    t.drones += 1;
+
+                    let goal = match t.drone_control {
+                        DroneControl::Attract => Some(t.firing_to),
+                        DroneControl::Idle => drone_targets.get(id).copied(),
+                        DroneControl::Repel => None,
+                    };
+                    let dir;
+                    if let Some(goal) = goal {
+                        // move drone in direction to the goal, slowing down near it
+                        let vdiff = vector_diff((d.physics.x, d.physics.y), goal);
                         if vector_lenght(vdiff) > 128. {
-                            dir = normalize(vector_diff((d.physics.x, d.physics.y), t.firing_to));
+                            dir = normalize(vdiff);
                         } else {
                             dir = (vdiff.0/128., vdiff.1/128.);
                         }
-                        d.physics.xvel += dir.0 * delta * 2048.;
-                        d.physics.yvel += dir.1 * delta * 2048.;
-                        d.physics.xvel *= (-delta).exp();
-                        d.physics.yvel *= (-delta).exp();
+                    } else if t.drone_control == DroneControl::Repel {
+                        dir = normalize(vector_diff(t.firing_to, (d.physics.x, d.physics.y)));
+                    } else {
+                        // circle around the tank, a few tank sizes away from it
+                        let away = vector_diff((t.physics.x, t.physics.y), (d.physics.x, d.physics.y));
+                        let dist = vector_lenght(away);
+                        let away = if dist > 1. { (away.0/dist, away.1/dist) } else { (0., -1.) };
+                        let pull = ((t.physics.collision_size*4. - dist)/128.).clamp(-1., 1.);
+                        dir = (away.0*pull - away.1*0.25, away.1*pull + away.0*0.25);
                     }
+                    d.physics.xvel += dir.0 * delta * 2048.;
+                    d.physics.yvel += dir.1 * delta * 2048.;
+                    d.physics.xvel *= (-delta).exp();
+                    d.physics.yvel *= (-delta).exp();
                 }
+                t.drone_control = DroneControl::Idle;
             }
 
             
@@ -1388,10 +2507,10 @@ impl Map {
                             let mut kp = (self.get_physics(&k).unwrap()).clone();
 
                             // mbombs
-                            if self.bullets.contains_key(&a) && self.bullets.get(&a).unwrap().texture == "mbomb" {
+                            if self.bullets.contains_key(&a) && matches!(self.bullets.get(&a).unwrap().kind, ProjectileKind::MagnetBomb(_)) {
                                 self.get_physics_mut(&a).unwrap().stick_to(&mut kp, delta*-0.5);
                             }
-                            else if self.bullets.contains_key(&k) && self.bullets.get(&k).unwrap().texture == "mbomb" {
+                            else if self.bullets.contains_key(&k) && matches!(self.bullets.get(&k).unwrap().kind, ProjectileKind::MagnetBomb(_)) {
                                 self.get_physics_mut(&k).unwrap().stick_to(&mut ap, delta*-0.5);
                             }
 
@@ -1399,19 +2518,28 @@ impl Map {
                             else if self.bullets.contains_key(&a) && self.bullets.contains_key(&k) {
                                 // can be used to handle some bullets differently
 
-                                if self.bullets.get(&a).unwrap().texture == "trap" || self.bullets.get(&k).unwrap().texture == "trap"
-                                || self.bullets.get(&a).unwrap().texture == "trapbomb" || self.bullets.get(&k).unwrap().texture == "trapbomb"
-                                || self.bullets.get(&a).unwrap().texture == "bomb" || self.bullets.get(&k).unwrap().texture == "bomb"
-                                 {
-                                    self.get_physics_mut(&k).unwrap().collide_position_only(&ap, delta);
-                                    self.get_physics_mut(&a).unwrap().collide_position_only(&mut kp, delta);
+                                if self.bullets.get(&a).unwrap().kind.blocks_projectiles() || self.bullets.get(&k).unwrap().kind.blocks_projectiles() {
+                                    self.get_physics_mut(&k).unwrap().collide_position_only(&ap, delta, 0.);
+                                    self.get_physics_mut(&a).unwrap().collide_position_only(&mut kp, delta, 0.);
                                 }
                             } else if self.tanks.contains_key(&a) && self.tanks.get(&a).unwrap().bullet_ids.contains(&k) || self.tanks.contains_key(&k) && self.tanks.get(&k).unwrap().bullet_ids.contains(&a) {
                                 // DISABLE
                             } else {
-                                // normal collision
-                                self.get_physics_mut(&k).unwrap().collide(&ap, delta);                       
-                                self.get_physics_mut(&a).unwrap().collide(&mut kp, delta);
+                                // normal collision, the gamemode decides who takes damage
+                                let (a_body_damage, a_resistance) = self.body_stats(*a);
+                                let (k_body_damage, k_resistance) = self.body_stats(k);
+                                if gamemode.can_damage(self, *a, k) {
+                                    self.get_physics_mut(&k).unwrap().collide(&ap, delta, a_body_damage, k_resistance);
+                                    self.record_damage(k, *a, kp.hp - self.get_physics(&k).unwrap().hp);
+                                } else {
+                                    self.get_physics_mut(&k).unwrap().collide_position_only(&ap, delta, k_resistance);
+                                }
+                                if gamemode.can_damage(self, k, *a) {
+                                    self.get_physics_mut(&a).unwrap().collide(&mut kp, delta, k_body_damage, a_resistance);
+                                    self.record_damage(*a, k, ap.hp - self.get_physics(a).unwrap().hp);
+                                } else {
+                                    self.get_physics_mut(&a).unwrap().collide_position_only(&mut kp, delta, a_resistance);
+                                }
                             }
 
                             // if k is a tank, and a is a bullet
@@ -1462,8 +2590,8 @@ impl Map {
                                     self.tanks.get_mut(&self.bullets.get(&k).unwrap().source_tank_id).unwrap().evolution.add_xp(self.shapes.get_mut(&a).unwrap().physics.collision_size.powi(2)*0.01);
 
                                     // infector tank
-                                    if self.tanks.get(&self.bullets.get(&k).unwrap().source_tank_id).unwrap().texture == "infector" && self.shapes.get(&a).unwrap().texture == "triangle" &&
-                                    self.tanks.get_mut(&self.bullets.get(&k).unwrap().source_tank_id).unwrap().bullet_ids.len() < 256 {
+                                    if self.tanks.get(&self.bullets.get(&k).unwrap().source_tank_id).unwrap().infects && self.shapes.get(&a).unwrap().texture == "triangle" &&
+                                    self.tanks.get_mut(&self.bullets.get(&k).unwrap().source_tank_id).unwrap().has_room_for_drone() {
                                         let uuid = thread_rng().gen::<u128>();
                                         let mut ph = self.shapes.get(&a).unwrap().physics.clone();
                                         ph.max_hp = (bullet.physics.max_hp * 2.).min(128.);
@@ -1472,7 +2600,10 @@ impl Map {
                                         self.bullets.insert(uuid, Bullet {
                                             physics: ph,
                                             source_tank_id: self.bullets.get(&k).unwrap().source_tank_id,
+                                            team: self.bullets.get(&k).unwrap().team,
                                             texture: "drone".to_owned(),
+                                            kind: ProjectileKind::Drone,
+                                            rocket: None,
                                         });
                                         self.tanks.get_mut(&self.bullets.get(&k).unwrap().source_tank_id).unwrap().bullet_ids.insert(uuid);
                                     }
@@ -1484,8 +2615,8 @@ impl Map {
                                     self.tanks.get_mut(&self.bullets.get(&a).unwrap().source_tank_id).unwrap().evolution.add_xp(self.shapes.get_mut(&k).unwrap().physics.collision_size.powi(2)*0.01);
 
                                     // infector tank
-                                    if self.tanks.get(&self.bullets.get(&a).unwrap().source_tank_id).unwrap().texture == "infector" && self.shapes.get(&k).unwrap().texture == "triangle" &&
-                                    self.tanks.get_mut(&self.bullets.get(&a).unwrap().source_tank_id).unwrap().bullet_ids.len() < 256 {
+                                    if self.tanks.get(&self.bullets.get(&a).unwrap().source_tank_id).unwrap().infects && self.shapes.get(&k).unwrap().texture == "triangle" &&
+                                    self.tanks.get_mut(&self.bullets.get(&a).unwrap().source_tank_id).unwrap().has_room_for_drone() {
                                         let uuid = thread_rng().gen::<u128>();
                                         let mut ph = self.shapes.get(&k).unwrap().physics.clone();
                                         ph.max_hp = (bullet.physics.max_hp * 2.).min(128.);
@@ -1494,7 +2625,10 @@ impl Map {
                                         self.bullets.insert(uuid, Bullet {
                                             physics: ph,
                                             source_tank_id: self.bullets.get(&a).unwrap().source_tank_id,
+                                            team: self.bullets.get(&a).unwrap().team,
                                             texture: "drone".to_owned(),
+                                            kind: ProjectileKind::Drone,
+                                            rocket: None,
                                         });
                                         self.tanks.get_mut(&self.bullets.get(&a).unwrap().source_tank_id).unwrap().bullet_ids.insert(uuid);
                                     }
@@ -1511,19 +2645,294 @@ impl Map {
         }
 
         for tank in self.tanks.values_mut() {
+            // tanks with stealth fade out while they stand still, and show up again when they move. Firing shows them in `Tank::fire`
+            if tank.stealth > 0. {
+                if tank.physics.speed() > STEALTH_STILL_SPEED {
+                    tank.visibility = 1.;
+                } else {
+                    tank.visibility = (tank.visibility - delta/tank.stealth).max(0.);
+                }
+            }
             for turret in &mut tank.turrets {
                 // substracts delta from time to next shot, but doesn't go below zero
                 turret.time_to_next_shot -= turret.time_to_next_shot.min(delta);
+                if !turret.triggered {
+                    turret.idle = true;
+                }
+                turret.triggered = false;
             }
         }
     }
 }
 
+/// Seconds between bosses in the ffa room of the server and in the local game
+const BOSS_INTERVAL: Option<f64> = Some(300.);
+
+/// Settings of a server without a window, see `run_server`
+struct ServerSettings {
+    /// every room has its own map, gamemode and bots
+    rooms: Vec<RoomSettings>,
+    tick_rate: f64,
+    /// browser clients connect here, the same game as the raw TCP address
+    websocket_address: Option<String>,
+}
+impl Default for ServerSettings {
+    fn default() -> Self {
+        ServerSettings {
+            rooms: vec![
+                RoomSettings {
+                    name: "ffa".to_owned(),
+                    gamemode: "ffa".to_owned(),
+                    map_size: (10_000., 10_000.),
+                    bots: 100,
+                    max_players: 32,
+                    shapes: true,
+                    zone: None,
+                    boss_interval: BOSS_INTERVAL,
+                    seed: None,
+                },
+                RoomSettings {
+                    name: "survival".to_owned(),
+                    gamemode: "survival".to_owned(),
+                    map_size: (5_000., 5_000.),
+                    bots: 50,
+                    max_players: 16,
+                    shapes: true,
+                    zone: Some(zone::default_schedule((5_000., 5_000.))),
+                    boss_interval: None,
+                    seed: None,
+                },
+                RoomSettings {
+                    name: "domination".to_owned(),
+                    gamemode: "domination".to_owned(),
+                    map_size: (5_000., 5_000.),
+                    bots: 40,
+                    max_players: 16,
+                    shapes: true,
+                    zone: None,
+                    boss_interval: None,
+                    seed: None,
+                },
+                RoomSettings {
+                    name: "maze".to_owned(),
+                    gamemode: "maze".to_owned(),
+                    map_size: (5_000., 5_000.),
+                    bots: 40,
+                    max_players: 16,
+                    shapes: true,
+                    zone: None,
+                    boss_interval: None,
+                    seed: None,
+                },
+                RoomSettings {
+                    name: "infection".to_owned(),
+                    gamemode: "infection".to_owned(),
+                    map_size: (4_000., 4_000.),
+                    bots: 30,
+                    max_players: 16,
+                    shapes: true,
+                    zone: None,
+                    boss_interval: None,
+                    seed: None,
+                },
+                RoomSettings {
+                    name: "ctf".to_owned(),
+                    gamemode: "ctf".to_owned(),
+                    map_size: (4_000., 2_000.),
+                    bots: 30,
+                    max_players: 16,
+                    shapes: true,
+                    zone: None,
+                    boss_interval: None,
+                    seed: None,
+                },
+                RoomSettings {
+                    name: "waves".to_owned(),
+                    gamemode: "waves".to_owned(),
+                    map_size: (4_000., 4_000.),
+                    bots: 0,
+                    max_players: 8,
+                    shapes: true,
+                    zone: None,
+                    boss_interval: None,
+                    seed: None,
+                },
+            ],
+            tick_rate: 60.,
+            websocket_address: Some("127.0.0.1:8081".to_owned()),
+        }
+    }
+}
+
+/// Runs the game without a window and without a local player, only network players and bots. Never returns.
+fn run_server(address: &str, settings: ServerSettings) {
+    let mut network_server = NetworkServer::start(address);
+    if let Some(websocket_address) = &settings.websocket_address {
+        network_server.listen_websocket(websocket_address);
+    }
+    let mut rooms: Vec<Room> = settings.rooms.into_iter().map(Room::new).collect();
+
+    let tick_time = 1. / settings.tick_rate;
+    loop {
+        let tick_start = Instant::now();
+
+        network_server.apply_inputs(&mut rooms, tick_time);
+        // empty rooms fall asleep
+        for room in rooms.iter_mut() {
+            room.update(tick_time);
+        }
+        network_server.send_snapshots(&rooms);
+
+        let elapsed = Instant::now().duration_since(tick_start).as_secs_f64();
+        if elapsed < tick_time {
+            thread::sleep(time::Duration::from_secs_f64(tick_time - elapsed));
+        }
+    }
+}
+
+/// Textures that are not a tank class drawn from `svg/<name>.svg`, by name
+const TEXTURE_FILES: [(&str, &str); 11] = [
+    ("bullet", "textures/bullet.png"),
+    ("trap", "textures/trap.png"),
+    ("bomb", "textures/bomb.png"),
+    ("mbomb", "textures/bomb.png"),
+    ("trapbomb", "textures/trap.png"),
+    ("drone", "textures/triangle.png"),
+    ("square", "textures/square.png"),
+    ("hexagon", "textures/hexagon.png"),
+    ("triangle", "textures/triangle.png"),
+    ("12gon", "textures/12gon.png"),
+    // wide not yet done
+    ("wide", "svg/basic.svg"),
+];
+
+/// Names of all the textures the game loads, without loading them
+fn texture_names() -> HashSet<String> {
+    let mut names: HashSet<String> = TEXTURE_FILES.iter().map(|(name, _)| name.to_string()).collect();
+    if let Ok(paths) = fs::read_dir("./svg/") {
+        for path in paths.flatten() {
+            let path = path.path();
+            if path.extension().is_some_and(|e| e == "svg") {
+                names.insert(path.file_stem().unwrap().to_string_lossy().into_owned());
+            }
+        }
+    }
+    names
+}
+
+/// Prints every problem of an evolution tree loaded from `dir`, see `tank_tree::validate`. Returns whether there were none
+fn check_classes(tree: &tank_tree::ClassTree, dir: &str) -> bool {
+    let problems = tank_tree::validate(tree, &texture_names(), &gamemode::required_classes());
+    if !problems.is_empty() {
+        eprintln!("the tank classes in {}/ have {} problems:", dir, problems.len());
+        for problem in &problems {
+            eprintln!("    {}", problem);
+        }
+    }
+    problems.is_empty()
+}
+
 fn main() {
     // INIT
 
-    // spawn network thread
-    thread::spawn(|| run_network());
+    let args: Vec<String> = std::env::args().collect();
+
+    // `--check-classes [dir]` only checks the class definitions, so a broken tree can be checked before using it
+    if args.get(1).map(|a| a.as_str()) == Some("--check-classes") {
+        let dir = args.get(2).map(|a| a.as_str()).unwrap_or(tank_tree::CLASSES_DIR);
+        match tank_tree::load_classes(dir) {
+            Ok(tree) => if check_classes(&tree, dir) {
+                println!("{} classes in {}/ are fine", tree.len(), dir);
+            } else {
+                std::process::exit(1);
+            },
+            Err(e) => {
+                eprintln!("could not load the tank classes from {}/: {}", dir, e);
+                std::process::exit(1);
+            }
+        }
+        return
+    }
+    // mistakes in the tree would otherwise only panic once someone evolves
+    if !check_classes(&EVOLUTION_TREE, tank_tree::CLASSES_DIR) {
+        std::process::exit(1);
+    }
+
+    match args.get(1).map(|a| a.as_str()) {
+        Some("--server") => {
+            let address = args.get(2).cloned().unwrap_or("127.0.0.1:8080".to_owned());
+            let mut settings = ServerSettings::default();
+            if let Some(websocket_address) = args.get(3) {
+                settings.websocket_address = Some(websocket_address.clone());
+            }
+            run_server(&address, settings);
+            return
+        }
+        Some("--netsim") => {
+            netsim::run_proxy_from_args(&args[2..]);
+            return
+        }
+        Some("--netsim-test") => {
+            if !netsim::run_convergence_test() {
+                std::process::exit(1);
+            }
+            return
+        }
+        Some("--websocket-test") => {
+            if !netsim::run_websocket_test() {
+                std::process::exit(1);
+            }
+            return
+        }
+        _ => {}
+    }
+
+    // the gamemode of the local game, `--gamemode <name>`
+    let local_gamemode = match args.iter().position(|a| a == "--gamemode") {
+        Some(i) => args.get(i + 1).cloned().unwrap_or_default(),
+        None => "ffa".to_owned(),
+    };
+    if gamemode::from_name(&local_gamemode).is_none() {
+        eprintln!("unknown gamemode {}, available gamemodes: {:?}", local_gamemode, gamemode::GAMEMODES);
+        return
+    }
+
+    // battle royale zone of the local game, `--zone <default|schedule.json>`
+    let local_zone = match args.iter().position(|a| a == "--zone").map(|i| args.get(i + 1).map(|a| a.as_str())) {
+        None => None,
+        Some(None) | Some(Some("default")) => Some(zone::default_schedule((10_000., 10_000.))),
+        Some(Some(path)) => match zone::load_schedule(path) {
+            Ok(schedule) => Some(schedule),
+            Err(e) => {
+                eprintln!("could not load the zone schedule {}: {}", path, e);
+                return
+            }
+        },
+    };
+
+    // seed of the local map, `--seed <number>`
+    let local_seed = match args.iter().position(|a| a == "--seed").map(|i| args.get(i + 1).and_then(|a| a.parse::<u64>().ok())) {
+        None => None,
+        Some(Some(seed)) => Some(seed),
+        Some(None) => {
+            eprintln!("--seed needs a number");
+            return
+        }
+    };
+
+    // max hp and hp regen of the sandbox dummies, `--dummy <hp> <regen>`
+    let local_dummy = match args.iter().position(|a| a == "--dummy").map(|i| (args.get(i + 1).and_then(|a| a.parse::<f64>().ok()), args.get(i + 2).and_then(|a| a.parse::<f64>().ok()))) {
+        None => None,
+        Some((Some(hp), Some(regen))) if hp > 0. => Some((hp, regen)),
+        Some(_) => {
+            eprintln!("--dummy needs the hp and the hp regen of the dummies, like --dummy 1000 0");
+            return
+        }
+    };
+
+    // spawn network thread, other players can join the local game over LAN
+    let mut network_server = NetworkServer::start("127.0.0.1:8080");
+    network_server.listen_websocket("127.0.0.1:8081");
 
     // Initialize sld2 related things
     let sdl_context = sdl2::init().unwrap();
@@ -1555,33 +2964,34 @@ fn main() {
         println!("{}", path[6..path.len()-4].to_string());
         textures.insert(path[6..path.len()-4].to_string(), texture_creator.load_texture(path).unwrap());
     }
-    
-    textures.insert("bullet".to_owned(), texture_creator.load_texture("textures/bullet.png").unwrap());
-    textures.insert("trap".to_owned(), texture_creator.load_texture("textures/trap.png").unwrap());
-    textures.insert("bomb".to_owned(), texture_creator.load_texture("textures/bomb.png").unwrap());
-    textures.insert("mbomb".to_owned(), texture_creator.load_texture("textures/bomb.png").unwrap());
-    textures.insert("trapbomb".to_owned(), texture_creator.load_texture("textures/trap.png").unwrap());
-    textures.insert("drone".to_owned(), texture_creator.load_texture("textures/triangle.png").unwrap());
-
-    textures.insert("square".to_owned(), texture_creator.load_texture("textures/square.png").unwrap());
-    textures.insert("hexagon".to_owned(), texture_creator.load_texture("textures/hexagon.png").unwrap());
-    textures.insert("triangle".to_owned(), texture_creator.load_texture("textures/triangle.png").unwrap());
-    textures.insert("12gon".to_owned(), texture_creator.load_texture("textures/12gon.png").unwrap());
 
-    // wide not yet done
-    textures.insert("wide".to_owned(), texture_creator.load_texture("svg/basic.svg").unwrap());
+    for (name, path) in TEXTURE_FILES {
+        textures.insert(name.to_owned(), texture_creator.load_texture(path).unwrap());
+    }
 
     // Initialize my own things
-    let mut map = Map {
+    // bosses would get in the way of testing
+    let local_boss_interval = if local_gamemode == "sandbox" {None} else {BOSS_INTERVAL};
+    let mut room = Room::new(RoomSettings {
+        name: "local".to_owned(),
+        gamemode: local_gamemode,
         map_size: (10_000., 10_000.,),
-        shapes_max: 0,
-        shapes: HashMap::new(),
-        tanks: HashMap::new(),
-        bullets: HashMap::new(),
-        tankais: vec![]
-    };
+        bots: 100,
+        max_players: 8,
+        shapes: true,
+        zone: local_zone,
+        boss_interval: local_boss_interval,
+        seed: local_seed,
+    });
+    if let (Some(sandbox), Some((hp, regen))) = (room.gamemode.sandbox(), local_dummy) {
+        sandbox.dummy_hp = hp;
+        sandbox.dummy_regen = regen;
+    }
     let mut input = Input::init();
+    // the last announcement of the map, and for how many more seconds it is shown
+    let mut announcement = (String::new(), 0.);
     let playerid: u128 = rng.gen();
+    room.add_player(playerid);
     let mut camera = Camera {
         x: 0.,
         y: 0.,
@@ -1640,119 +3050,23 @@ fn main() {
             }
         }
 
-        // SPAWN TANKS
+        // SPAWN PLAYER
 
-        while map.tanks.len() < 100 && (GAMEMODE == Gamemode::FFA || delta == 0.01 ) {
-            let ai_tank_id = thread_rng().gen::<u128>();
+        // the gamemode decides if and where the player respawns
+        room.spawn_player(playerid);
 
-            // add AI tank
-            // tanks will be network or AI controlled on the server (also player controlled on LAN multiplayer server), and player or AI controlled in singleplayer
-            let class = "basic";
-            map.tanks.insert(
-                ai_tank_id,
-                EVOLUTION_TREE.get(&class.to_owned()).unwrap().0.clone()
-            );
-            let ph = &mut map.tanks.get_mut(&ai_tank_id).unwrap().physics;
-            ph.x = thread_rng().gen::<f64>()*map.map_size.0*2. - map.map_size.0;
-            ph.y = thread_rng().gen::<f64>()*map.map_size.1*2. - map.map_size.1;
-            // will be clamped to max hp automatically
-            ph.hp = 10000.;
-
-            let ev = &mut map.tanks.get_mut(&ai_tank_id).unwrap().evolution;
-            ev.hp_level = thread_rng().gen_range(0..1);
-            ev.regen_level = thread_rng().gen_range(0..1);
-            ev.reload_level = thread_rng().gen_range(0..1);
-            ev.damage_level = thread_rng().gen_range(0..1);
-            ev.speed_level = thread_rng().gen_range(0..1);
-            ev.bulletspeed_level = thread_rng().gen_range(0..1);
-            ev.class = class.to_owned();
-
-            let tank =&mut map.tanks.get_mut(&ai_tank_id).unwrap();
-            Evolution::level_refresh(tank);            
-
-            map.tankais.push(TankAI {
-                id: ai_tank_id,
-                range: 3072.,
-                tg_range: if tank.evolution.class == "shotgun" {
-                    128.
-                } else {
-                    (tank.turrets[0].projectile_impulse/tank.turrets[0].projectile_weight).sqrt()  *  (tank.turrets[0].projectile_hp/-tank.turrets[0].projectile_hp_regen).sqrt()  *  8.
-                },
-                bullet_speed: tank.turrets[0].projectile_impulse/tank.turrets[0].projectile_weight,
-                dodge_obstacles: true,
-                fighting: true,
-                tg_id: 0,
-                next_upgrade_is_promotion: false
-            });
-        }
+        // NETWORK PLAYERS
 
-        if !map.tanks.contains_key(&playerid) {
-            // add player
-            map.tanks.insert(
-                playerid,
-                EVOLUTION_TREE.get(&"basic".to_string()).unwrap().0.clone()
-            );
-
-            let ph = &mut map.tanks.get_mut(&playerid).unwrap().physics;
-            ph.x = thread_rng().gen::<f64>()*map.map_size.0*2. - map.map_size.0;
-            ph.y = thread_rng().gen::<f64>()*map.map_size.1*2. - map.map_size.1;
-            // will be clamped to max hp automatically
-            // ph.hp = 10000.;
-
-            // let ev = &mut map.tanks.get_mut(&playerid).unwrap().evolution;
-            // ev.hp_level = thread_rng().gen_range(10..11);
-            // ev.regen_level = thread_rng().gen_range(10..11);
-            // ev.reload_level = thread_rng().gen_range(10..11);
-            // ev.damage_level = thread_rng().gen_range(10..11);
-            // ev.speed_level = thread_rng().gen_range(10..11);
-            // ev.bulletspeed_level = thread_rng().gen_range(10..11);
-
-            Evolution::level_refresh(map.tanks.get_mut(&playerid).unwrap()); 
-
-        }
-        
+        network_server.apply_inputs(std::slice::from_mut(&mut room), delta);
 
         // PLAYER CONTROL
 
-        if map.tanks.contains_key(&playerid) {
-            let player = map.tanks.get_mut(&playerid).unwrap();
+        if room.map.tanks.contains_key(&playerid) {
+            let player = room.map.tanks.get_mut(&playerid).unwrap();
 
-            //movement
-            if input.up.is_down && input.left.is_down && !input.down.is_down && !input.right.is_down {
-                player.move_in_dir((-0.707,-0.707), delta);
-            }
-            else if input.down.is_down && input.left.is_down && !input.up.is_down && !input.right.is_down {
-                player.move_in_dir((-0.707,0.707), delta);
-            }
-            else if input.up.is_down && input.right.is_down && !input.down.is_down && !input.left.is_down {
-                player.move_in_dir((0.707,-0.707), delta);
-            }
-            else if input.down.is_down && input.right.is_down && !input.up.is_down && !input.left.is_down {
-                player.move_in_dir((0.707,0.707), delta);
-            }
-            else if input.up.is_down {
-                player.move_in_dir((0.,-1.), delta);
-            }
-            else if input.down.is_down {
-                player.move_in_dir((0.,1.), delta);
-            }
-            else if input.left.is_down {
-                player.move_in_dir((-1.,0.), delta);
-            }
-            else if input.right.is_down {
-                player.move_in_dir((1.,0.), delta);
-            }
-            else {
-                // brake
-                player.move_in_dir((0.,0.), delta);
-            }
-
-            //rotation
-            player.rotate_to(camera.to_map_coords(input.mouse_pos), delta);
-
-            //firing
-            if input.fire.is_down {
-                player.fire(&mut map.bullets, playerid);
+            // movement, rotation and firing are the same for local and network players
+            if !room.gamemode.paused() {
+                player.control(&input.to_network(&camera), &mut room.map.bullets, playerid, delta);
             }
 
             // Evolution
@@ -1824,23 +3138,48 @@ fn main() {
             }
         }
 
-        // AI CONTROL
+        // SANDBOX TOOLS
 
-        // this will call all the AIs' control functions, and keep only the AIs that return true
-        map.tankais.retain_mut(|ai |ai.control(&mut map.tanks, &mut map.shapes, &mut map.bullets, delta));
+        if let Some(sandbox) = room.gamemode.sandbox() {
+            let mouse = camera.to_map_coords(input.mouse_pos);
+            if input.class.is_down && input.class.just {
+                sandbox.cycle_class(&mut room.map, playerid, if input.shift.is_down {-1} else {1});
+            }
+            for (button, kind) in [input.spawn_square, input.spawn_triangle, input.spawn_hexagon, input.spawn_12gon].into_iter().zip(SHAPE_KINDS) {
+                if button.is_down && button.just {
+                    sandbox.spawn_shape(&mut room.map, kind, mouse);
+                }
+            }
+            if input.spawn_dummy.is_down && input.spawn_dummy.just {
+                sandbox.spawn_dummy(&mut room.map, mouse);
+            }
+            if input.invulnerable.is_down && input.invulnerable.just {
+                sandbox.toggle_invulnerable(playerid);
+            }
+            if input.freeze.is_down && input.freeze.just {
+                sandbox.frozen = !sandbox.frozen;
+            }
+            if input.bots.is_down && input.bots.just {
+                sandbox.bots = !sandbox.bots;
+            }
+        }
 
+        // BOTS, AI CONTROL, PHYSICS AND GAMEMODE
 
-        // PHYSICS
+        room.update(delta);
+        if let Some(last) = room.map.announcements.last() {
+            announcement = (last.clone(), 5.);
+        }
+        announcement.1 -= delta;
 
-        // at the end of physics, update all physics
-        map.update_physics(delta);
+        network_server.send_snapshots(std::slice::from_ref(&room));
 
 
         // CAMERA
 
         // track tg tank if it exists, otherwise don't move
-        if map.tanks.contains_key(&camera.target_tank) {
-            camera.track(delta, &map.tanks.get(&camera.target_tank).unwrap().physics);
+        if room.map.tanks.contains_key(&camera.target_tank) {
+            camera.track(delta, &room.map.tanks.get(&camera.target_tank).unwrap().physics);
             if input.zoom_out.is_down && input.zoom_out.just {
                 camera.zoom *= 0.96;
             }
@@ -1858,35 +3197,69 @@ fn main() {
         canvas.clear();
 
         // render map walls and grid
-        map.render(&mut canvas, &camera);
+        room.map.render(&mut canvas, &camera);
+        room.gamemode.render(&mut canvas, &camera);
 
         // Render all bullets
-        for bullet in map.bullets.iter().filter(|(id, b)| camera.visible((b.physics.x, b.physics.y), b.physics.collision_size)) {
+        for bullet in room.map.bullets.iter().filter(|(id, b)| camera.visible((b.physics.x, b.physics.y), b.physics.collision_size)) {
             bullet.1.render(&mut canvas, &camera, &textures);
         }
 
         // Render all shapes
-        for bullet in map.shapes.iter().filter(|(id, b)| camera.visible((b.physics.x, b.physics.y), b.physics.collision_size)) {
+        for bullet in room.map.shapes.iter().filter(|(id, b)| camera.visible((b.physics.x, b.physics.y), b.physics.collision_size)) {
             bullet.1.render(&mut canvas, &camera, &textures);
         }
 
         // Render all tanks
-        for tank in map.tanks.iter().filter(|(id, b)| camera.visible((b.physics.x, b.physics.y), b.physics.collision_size)) {
-            tank.1.render(&mut canvas, &camera, &textures);
+        // fading tanks are drawn transparent, and not at all when they are too far from the player to be seen
+        let player_tank = room.map.tanks.get(&playerid);
+        for tank in room.map.tanks.iter().filter(|(id, b)| camera.visible((b.physics.x, b.physics.y), b.physics.collision_size)) {
+            let hidden = match player_tank {
+                Some(player) => tank.1.hidden_at(&player.physics) && !tank.1.is_teammate(player),
+                None => tank.1.visibility <= 0.,
+            };
+            if hidden {
+                continue
+            }
+            tank.1.render(&mut canvas, &camera, &mut textures);
+        }
+
+        for beam in &room.map.beams {
+            beam.render(&mut canvas, &camera);
         }
 
+        room.map.render_minimap(&mut canvas, &camera, playerid);
+
+        // gamemode status like the survival countdown, and announcements below it
+        let status = room.gamemode.status(&room.map);
+        let announcement_text = if announcement.1 > 0. {announcement.0.as_str()} else {""};
+        for (i, line) in [status.as_str(), announcement_text].into_iter().enumerate().filter(|(_, l)| !l.is_empty()) {
+            let surface = font
+                .render(line)
+                .blended(Color::RGB(255, 255, 255))
+                .map_err(|e| e.to_string())
+                .unwrap();
+            let texture_creator: TextureCreator<_> = canvas.texture_creator();
+            let texture = texture_creator
+                .create_texture_from_surface(&surface)
+                .map_err(|e| e.to_string())
+                .unwrap();
+            let texture_query = texture.query();
+            let dest_rect = Rect::new(camera.viewport_size.0/2 - texture_query.width as i32/2, 40 + i as i32 * 60, texture_query.width, texture_query.height);
+            canvas.copy(&texture, None, dest_rect).unwrap();
+        }
 
         // render text info, later will be better
-        if map.tanks.contains_key(&playerid) && input.evolve.is_down {
-            let xp = map.tanks.get(&playerid).unwrap().evolution.xp;
-            let class = map.tanks.get(&playerid).unwrap().evolution.class.to_uppercase();
-            let hp_level = map.tanks.get(&playerid).unwrap().evolution.hp_level;
-            let regen_level = map.tanks.get(&playerid).unwrap().evolution.regen_level;
-            let reload_level = map.tanks.get(&playerid).unwrap().evolution.reload_level;
-            let damage_level = map.tanks.get(&playerid).unwrap().evolution.damage_level;
-            let speed_level = map.tanks.get(&playerid).unwrap().evolution.speed_level;
-            let bulletspeed_level = map.tanks.get(&playerid).unwrap().evolution.bulletspeed_level;
-            let players = map.tanks.len();
+        if room.map.tanks.contains_key(&playerid) && input.evolve.is_down {
+            let xp = room.map.tanks.get(&playerid).unwrap().evolution.xp;
+            let class = room.map.tanks.get(&playerid).unwrap().evolution.class.to_uppercase();
+            let hp_level = room.map.tanks.get(&playerid).unwrap().evolution.hp_level;
+            let regen_level = room.map.tanks.get(&playerid).unwrap().evolution.regen_level;
+            let reload_level = room.map.tanks.get(&playerid).unwrap().evolution.reload_level;
+            let damage_level = room.map.tanks.get(&playerid).unwrap().evolution.damage_level;
+            let speed_level = room.map.tanks.get(&playerid).unwrap().evolution.speed_level;
+            let bulletspeed_level = room.map.tanks.get(&playerid).unwrap().evolution.bulletspeed_level;
+            let players = room.map.tanks.len();
 
             let text = format!(
                 "XP: {:.0}\nClass: {}\n\nLevels:\nMAX HP: {}\nHP REGENERATION: {}\nRELOAD SPEED: {}\nBULLET DAMAGE: {}\nMOVEMENT SPEED: {}\nBULLET SPEED: {}, players: {}",
@@ -1921,10 +3294,10 @@ fn main() {
             // leaderbord
 
             let mut best_players: Vec<f64> = 
-            map.tanks.iter().map(|t| t.1.evolution.killvalue*2.).collect();
+            room.map.tanks.iter().map(|t| t.1.evolution.killvalue*2.).collect();
             best_players.sort_by(|a, b| b.partial_cmp(a).unwrap());
             println!("top tank");
-            // for t in map.tanks.values() {
+            // for t in room.map.tanks.values() {
             //     if t.evolution.killvalue*2. == best_players[0] {
             //         println!("evolution: {:?}", t)
             //     }
@@ -1974,15 +3347,41 @@ fn main() {
 
         // TEST PRINTS
         // println!("fps: {:.0}", 1./delta);
+    }
+}
 
-        // gamemode stuff
-
-        // Survival map gets smaller
-        if GAMEMODE == Gamemode::Survival {
-            map.map_size.0 -= (delta * 16.).min(map.map_size.0*delta/128.);
-            map.map_size.1 -= (delta * 16.).min(map.map_size.1*delta/128.);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn laser_kill_gives_xp() {
+        let mut map = Map::new((2000., 2000.));
+        let gamemode = gamemode::from_name("ffa").unwrap();
+        map.spawn_tank(1, "laser");
+        map.spawn_tank(2, "basic");
+        let shooter = map.tanks.get_mut(&1).unwrap();
+        shooter.physics.x = 0.;
+        shooter.physics.y = 0.;
+        shooter.physics.rot = 0.;
+        let victim = map.tanks.get_mut(&2).unwrap();
+        victim.physics.x = 0.;
+        victim.physics.y = -300.;
+        victim.physics.hp = 1.;
+        victim.evolution.killvalue = 100.;
+
+        let xp = map.tanks[&1].evolution.xp;
+        map.tanks.get_mut(&1).unwrap().fire(&mut HashMap::new(), 1);
+        for _ in 0..60 {
+            map.fire_lasers(&*gamemode, 1./60.);
         }
+        assert!(map.tanks[&2].physics.hp < 0.);
+        assert_eq!(map.tanks[&1].evolution.xp, xp + 100.);
+    }
 
-        map.shapes_max = ((map.map_size.0 * map.map_size.1) / 16384.) as usize;
+    #[test]
+    fn shipped_classes() {
+        let tree = tank_tree::load_classes(tank_tree::CLASSES_DIR).unwrap();
+        assert!(check_classes(&tree, tank_tree::CLASSES_DIR));
     }
 }
@@ -0,0 +1,484 @@
+use rand::prelude::*;
+use rand_distr::Distribution;
+use std::collections::BTreeMap;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::net::{TcpListener, TcpStream, Shutdown};
+use std::io::{Read, Write};
+
+use crate::network::{take_message, Connection, InputOverNetwork, SnapshotOverNetwork, TcpConnection, WebSocketConnection};
+use crate::room::RoomSettings;
+use crate::{run_server, ServerSettings};
+
+/// What the simulated network does to the messages. Applied to each direction separately, and to each message separately.
+///
+/// Works on whole messages (one JSON object), not on TCP packets, so a dropped message never breaks the messages after it.
+#[derive(Clone, Copy, Debug)]
+pub struct LinkConditions {
+    /// in seconds, one way
+    pub latency: f64,
+    /// in seconds, standard deviation of the gaussian added to the latency. Big jitter also reorders messages
+    pub jitter: f64,
+    /// from 0 to 1, chance of a message being dropped
+    pub loss: f64,
+    /// from 0 to 1, chance of a message being delivered twice
+    pub duplication: f64,
+    /// from 0 to 1, chance of a message skipping the latency, arriving before the messages sent earlier
+    pub reordering: f64,
+}
+impl Default for LinkConditions {
+    /// a perfect network
+    fn default() -> Self {
+        LinkConditions {
+            latency: 0.,
+            jitter: 0.,
+            loss: 0.,
+            duplication: 0.,
+            reordering: 0.,
+        }
+    }
+}
+impl LinkConditions {
+    /// Returns after how long each copy of a message is delivered. Empty if the message is lost
+    fn delays(&self) -> Vec<Duration> {
+        let mut rng = thread_rng();
+        if rng.gen_bool(self.loss.clamp(0., 1.)) {
+            return vec![]
+        }
+        let copies = if rng.gen_bool(self.duplication.clamp(0., 1.)) {2} else {1};
+
+        let mut delays = vec![];
+        for _ in 0..copies {
+            let mut delay = self.latency;
+            if self.jitter > 0. {
+                let normal = rand_distr::Normal::new(0., self.jitter).expect("Invalid parameters for normal distribution");
+                delay += normal.sample(&mut rng);
+            }
+            if rng.gen_bool(self.reordering.clamp(0., 1.)) {
+                delay = 0.;
+            }
+            delays.push(Duration::from_secs_f64(delay.max(0.)));
+        }
+        delays
+    }
+}
+
+/// Reads messages from `from`, and passes them to `to` changed by `conditions`. Returns when `from` closes
+fn pump(mut from: TcpStream, to: TcpStream, conditions: LinkConditions) {
+    let (scheduled, schedule) = mpsc::channel::<(Instant, String)>();
+    let deliver_thread = thread::spawn(move || deliver(to, schedule));
+
+    let mut buffer = [0; 4096];
+    let mut data = Vec::new();
+    loop {
+        let bytes_read = match from.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(bytes_read) => bytes_read,
+        };
+        data.extend_from_slice(&buffer[..bytes_read]);
+
+        while let Some(message) = take_message(&mut data) {
+            for delay in conditions.delays() {
+                let _ = scheduled.send((Instant::now() + delay, message.clone()));
+            }
+        }
+    }
+
+    // the deliver thread sends what is left and closes `to`
+    drop(scheduled);
+    let _ = deliver_thread.join();
+}
+
+/// Writes the scheduled messages to `to` once they are due, in the order they are due in
+fn deliver(mut to: TcpStream, schedule: mpsc::Receiver<(Instant, String)>) {
+    let mut pending: Vec<(Instant, String)> = vec![];
+    let mut sender_closed = false;
+    loop {
+        match schedule.recv_timeout(Duration::from_millis(1)) {
+            Ok(message) => pending.push(message),
+            Err(mpsc::RecvTimeoutError::Timeout) => {},
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                sender_closed = true;
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        pending.sort_by_key(|(due, _)| *due);
+        let now = Instant::now();
+        while !pending.is_empty() && pending[0].0 <= now {
+            let (_, message) = pending.remove(0);
+            if to.write_all(message.as_bytes()).is_err() {
+                return
+            }
+        }
+
+        if sender_closed && pending.is_empty() {
+            break
+        }
+    }
+    let _ = to.shutdown(Shutdown::Both);
+}
+
+/// Accepts clients on `listen_address` and connects each of them to `server_address`, passing messages both ways through a simulated network. Never returns.
+pub fn run_proxy(listen_address: &str, server_address: &str, conditions: LinkConditions) {
+    let listener = TcpListener::bind(listen_address).unwrap();
+    println!("Network simulator listening on {}, forwarding to {}, {:?}", listen_address, server_address, conditions);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(client) => {
+                let server = match TcpStream::connect(server_address) {
+                    Ok(server) => server,
+                    Err(e) => {
+                        println!("Failed to connect to server: {}", e);
+                        continue
+                    }
+                };
+                let client_reader = client.try_clone().unwrap();
+                let server_reader = server.try_clone().unwrap();
+                thread::spawn(move || pump(client_reader, server, conditions));
+                thread::spawn(move || pump(server_reader, client, conditions));
+            }
+            Err(e) => {
+                println!("Error: {}", e);
+            }
+        }
+    }
+}
+
+/// `args` are the command line arguments after `--netsim`:
+///
+/// `<listen address> <server address> [--latency ms] [--jitter ms] [--loss 0..1] [--duplicate 0..1] [--reorder 0..1]`
+pub fn run_proxy_from_args(args: &[String]) {
+    let usage = "usage: --netsim <listen address> <server address> [--latency ms] [--jitter ms] [--loss 0..1] [--duplicate 0..1] [--reorder 0..1]";
+    if args.len() < 2 {
+        eprintln!("{}", usage);
+        return
+    }
+
+    let mut conditions = LinkConditions::default();
+    for option in args[2..].chunks(2) {
+        let value = match option.get(1).and_then(|v| v.parse::<f64>().ok()) {
+            Some(value) => value,
+            None => {
+                eprintln!("{}", usage);
+                return
+            }
+        };
+        match option[0].as_str() {
+            "--latency" => conditions.latency = value / 1000.,
+            "--jitter" => conditions.jitter = value / 1000.,
+            "--loss" => conditions.loss = value,
+            "--duplicate" => conditions.duplication = value,
+            "--reorder" => conditions.reordering = value,
+            _ => {
+                eprintln!("{}", usage);
+                return
+            }
+        }
+    }
+
+    run_proxy(&args[0], &args[1], conditions);
+}
+
+/// A client without a window, that sends scripted inputs and stores all the snapshots it receives
+struct ScriptedClient {
+    connection: Box<dyn Connection>,
+    /// by tick, so duplicated snapshots overwrite themselves and late ones end up in the right place
+    snapshots: BTreeMap<u64, SnapshotOverNetwork>,
+}
+impl ScriptedClient {
+    /// Tries for a second, in case the server is not listening yet
+    fn connect(address: &str, websocket: bool) -> Option<Self> {
+        for _ in 0..20 {
+            let connection: Option<Box<dyn Connection>> = if websocket {
+                WebSocketConnection::connect(address).ok().map(|c| Box::new(c) as Box<dyn Connection>)
+            } else {
+                TcpStream::connect(address).ok().map(|s| Box::new(TcpConnection::new(s)) as Box<dyn Connection>)
+            };
+            if let Some(connection) = connection {
+                return Some(ScriptedClient {
+                    connection,
+                    snapshots: BTreeMap::new(),
+                })
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        None
+    }
+
+    fn send(&mut self, input: &InputOverNetwork) {
+        let _ = self.connection.send(&serde_json::to_string(input).unwrap());
+    }
+
+    /// Reads everything that arrived so far
+    fn receive(&mut self) {
+        for message in self.connection.receive().unwrap_or_default() {
+            if let Ok(snapshot) = serde_json::from_str::<SnapshotOverNetwork>(&message) {
+                self.snapshots.insert(snapshot.tick, snapshot);
+            }
+        }
+    }
+
+    fn latest(&self) -> Option<&SnapshotOverNetwork> {
+        self.snapshots.values().next_back()
+    }
+
+    /// The id of this client's tank, known after the first snapshot
+    fn id(&self) -> Option<u128> {
+        self.latest().map(|s| s.your_id)
+    }
+}
+
+/// Position and velocity of the tank `id` in a snapshot
+fn tank_state(snapshot: &SnapshotOverNetwork, id: u128) -> Option<(f64, f64, f64, f64)> {
+    snapshot.tanks.iter().find(|t| t.id == id).map(|t| (t.x, t.y, t.xvel, t.yvel))
+}
+
+/// Runs a server without bots and shapes, a bad network in front of it, and two scripted clients, one through the bad network and one directly.
+///
+/// The client behind the bad network drives its tank towards the center of the map and then stops. The test checks that both clients end up seeing the same state,
+/// that the lagging client is not too far behind, and that the server followed the inputs despite lost, duplicated and reordered messages.
+///
+/// Returns true if all checks passed. Run with `--netsim-test`
+pub fn run_convergence_test() -> bool {
+    let server_address = "127.0.0.1:18080";
+    let proxy_address = "127.0.0.1:18081";
+    let conditions = LinkConditions {
+        latency: 0.08,
+        jitter: 0.02,
+        loss: 0.1,
+        duplication: 0.05,
+        reordering: 0.05,
+    };
+    // snapshots are sent every tick, so at 60 ticks per second this is about half a second
+    let max_lag_ticks = 30;
+
+    thread::spawn(move || run_server(server_address, ServerSettings {
+        rooms: vec![RoomSettings {
+            name: "test".to_owned(),
+            gamemode: "ffa".to_owned(),
+            // small enough that both clients see the whole map
+            map_size: (1000., 1000.),
+            bots: 0,
+            max_players: 2,
+            shapes: false,
+            zone: None,
+            boss_interval: None,
+            seed: None,
+        }],
+        tick_rate: 60.,
+        websocket_address: None,
+    }));
+    thread::spawn(move || run_proxy(proxy_address, server_address, conditions));
+
+    let (mut lagging, mut direct) = match (ScriptedClient::connect(proxy_address, false), ScriptedClient::connect(server_address, false)) {
+        (Some(lagging), Some(direct)) => (lagging, direct),
+        _ => {
+            println!("FAIL: could not connect to the test server");
+            return false
+        }
+    };
+
+    let idle = InputOverNetwork::default();
+    let frame = Duration::from_millis(16);
+
+    // wait until the lagging client knows where its tank is
+    let start = Instant::now();
+    let mut first_state = None;
+    while first_state.is_none() && start.elapsed() < Duration::from_secs(5) {
+        lagging.send(&idle);
+        direct.send(&idle);
+        lagging.receive();
+        direct.receive();
+        if let (Some(id), Some(latest)) = (lagging.id(), lagging.latest()) {
+            first_state = tank_state(latest, id);
+        }
+        thread::sleep(frame);
+    }
+    let (id, first_state) = match (lagging.id(), first_state) {
+        (Some(id), Some(first_state)) => (id, first_state),
+        _ => {
+            println!("FAIL: the client behind the simulated network never received its tank");
+            return false
+        }
+    };
+
+    // drive towards the center for 1.5 seconds, then brake for 3 seconds
+    let to_right = first_state.0 < 0.;
+    let drive = InputOverNetwork {
+        left_pressed: !to_right,
+        right_pressed: to_right,
+        ..Default::default()
+    };
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(4500) {
+        if start.elapsed() < Duration::from_millis(1500) {
+            lagging.send(&drive);
+        } else {
+            lagging.send(&idle);
+        }
+        direct.send(&idle);
+        lagging.receive();
+        direct.receive();
+        thread::sleep(frame);
+    }
+
+    let mut passed = true;
+
+    let (lagging_latest, direct_latest) = match (lagging.latest(), direct.latest()) {
+        (Some(l), Some(d)) => (l, d),
+        _ => {
+            println!("FAIL: a client received no snapshots");
+            return false
+        }
+    };
+
+    // the lagging client keeps up
+    let lag = direct_latest.tick as i64 - lagging_latest.tick as i64;
+    if lag > max_lag_ticks {
+        println!("FAIL: the client behind the simulated network is {} ticks behind", lag);
+        passed = false;
+    } else {
+        println!("ok: the client behind the simulated network is {} ticks behind", lag);
+    }
+
+    // both clients saw the same state of the same tick
+    let common_tick = lagging.snapshots.keys().rev().find(|tick| direct.snapshots.contains_key(tick));
+    match common_tick.map(|tick| (tank_state(&lagging.snapshots[tick], id), tank_state(&direct.snapshots[tick], id))) {
+        Some((Some(l), Some(d))) if (l.0 - d.0).abs() < 0.001 && (l.1 - d.1).abs() < 0.001 => {
+            println!("ok: both clients agree on tick {}", common_tick.unwrap());
+        }
+        _ => {
+            println!("FAIL: the clients do not agree on any recent tick");
+            passed = false;
+        }
+    }
+
+    // the server followed the inputs, as seen by the direct client
+    match tank_state(direct_latest, id) {
+        Some(last_state) => {
+            let moved = if to_right {last_state.0 - first_state.0} else {first_state.0 - last_state.0};
+            if moved < 100. {
+                println!("FAIL: the tank moved only {:.0} towards the center", moved);
+                passed = false;
+            } else {
+                println!("ok: the tank moved {:.0} towards the center", moved);
+            }
+
+            let speed = (last_state.2.powi(2) + last_state.3.powi(2)).sqrt();
+            if speed > 50. {
+                println!("FAIL: the tank did not stop, speed {:.0}", speed);
+                passed = false;
+            } else {
+                println!("ok: the tank stopped, speed {:.0}", speed);
+            }
+        }
+        None => {
+            println!("FAIL: the direct client does not see the tank of the other client");
+            passed = false;
+        }
+    }
+
+    println!("{}", if passed {"network convergence test passed"} else {"network convergence test FAILED"});
+    passed
+}
+
+/// Runs a server without bots and shapes, and connects one client over WebSocket and one over raw TCP.
+///
+/// The WebSocket client drives its tank for a second. The test checks that it receives snapshots, that its tank moved, and that the TCP client sees the same tank, so both transports play in the same game.
+///
+/// Returns true if all checks passed. Run with `--websocket-test`
+pub fn run_websocket_test() -> bool {
+    let server_address = "127.0.0.1:18090";
+    let websocket_address = "127.0.0.1:18091";
+
+    thread::spawn(move || run_server(server_address, ServerSettings {
+        rooms: vec![RoomSettings {
+            name: "test".to_owned(),
+            gamemode: "ffa".to_owned(),
+            map_size: (1000., 1000.),
+            bots: 0,
+            max_players: 2,
+            shapes: false,
+            zone: None,
+            boss_interval: None,
+            seed: None,
+        }],
+        tick_rate: 60.,
+        websocket_address: Some(websocket_address.to_owned()),
+    }));
+
+    let (mut browser, mut native) = match (ScriptedClient::connect(websocket_address, true), ScriptedClient::connect(server_address, false)) {
+        (Some(browser), Some(native)) => (browser, native),
+        _ => {
+            println!("FAIL: could not connect to the test server");
+            return false
+        }
+    };
+
+    let drive = InputOverNetwork {
+        up_pressed: true,
+        ..Default::default()
+    };
+    let idle = InputOverNetwork::default();
+    let start = Instant::now();
+    let mut first_y = None;
+    while start.elapsed() < Duration::from_millis(1500) {
+        browser.send(&drive);
+        native.send(&idle);
+        browser.receive();
+        native.receive();
+        if first_y.is_none() {
+            if let (Some(id), Some(latest)) = (browser.id(), browser.latest()) {
+                first_y = tank_state(latest, id).map(|s| s.1);
+            }
+        }
+        thread::sleep(Duration::from_millis(16));
+    }
+
+    let mut passed = true;
+
+    let (id, first_y) = match (browser.id(), first_y) {
+        (Some(id), Some(first_y)) => (id, first_y),
+        _ => {
+            println!("FAIL: the WebSocket client did not receive its tank");
+            return false
+        }
+    };
+    println!("ok: the WebSocket client received {} snapshots", browser.snapshots.len());
+
+    match browser.latest().and_then(|s| tank_state(s, id)) {
+        Some(last_state) if first_y - last_state.1 > 20. => {
+            println!("ok: the WebSocket client's tank moved {:.0} up", first_y - last_state.1);
+        }
+        _ => {
+            println!("FAIL: the WebSocket client's tank did not follow its input");
+            passed = false;
+        }
+    }
+
+    match native.latest().and_then(|s| tank_state(s, id)) {
+        Some(_) => println!("ok: the TCP client sees the WebSocket client's tank"),
+        None => {
+            println!("FAIL: the TCP client does not see the WebSocket client's tank");
+            passed = false;
+        }
+    }
+
+    println!("{}", if passed {"WebSocket test passed"} else {"WebSocket test FAILED"});
+    passed
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn convergence() {
+        assert!(super::run_convergence_test());
+    }
+
+    #[test]
+    fn websocket() {
+        assert!(super::run_websocket_test());
+    }
+}
@@ -1,88 +1,530 @@
 use rand::prelude::*;
-use std::fs::File;
+use std::collections::HashMap;
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
+use std::time::Duration;
 use std::net::{TcpListener, TcpStream, Shutdown};
-use std::io::{Read, Write};
+use std::io::{Read, Write, ErrorKind};
 
 use serde::{Serialize, Deserialize};
+use tungstenite::{Message, WebSocket};
 
-#[derive(Debug, Serialize, Deserialize)]
-struct InputOverNetwork {
-    up_pressed: bool,
-    up_just: bool,
-    down_pressed: bool,
-    down_just: bool,
-    left_pressed: bool,
-    left_just: bool,
-    right_pressed: bool,
-    right_just: bool,
-    mousepos: (f64, f64),
+use crate::Beam;
+use crate::Map;
+use crate::Physics;
+use crate::room::Room;
+use crate::walls::Wall;
+
+/// Objects further than this from the tank of a client are not sent to that client
+pub const SNAPSHOT_RADIUS: f64 = 3000.;
+
+/// Sent by the client every frame. Contains the state of the controls and not events, so a lost or late message is corrected by the next one
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InputOverNetwork {
+    pub up_pressed: bool,
+    pub up_just: bool,
+    pub down_pressed: bool,
+    pub down_just: bool,
+    pub left_pressed: bool,
+    pub left_just: bool,
+    pub right_pressed: bool,
+    pub right_just: bool,
+    pub fire_pressed: bool,
+    /// pushes drones away from the mouse, see `DroneControl`
+    #[serde(default)]
+    pub repel_pressed: bool,
+    /// in map coordinates, the server does not know the camera of the client
+    pub mousepos: (f64, f64),
+}
+
+/// Sent by the client to pick a room, at any time. Clients that send inputs without joining are matched to a room automatically
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JoinOverNetwork {
+    /// name of the room
+    pub join: String,
+}
+
+/// A room as listed to clients, so they can pick one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomOverNetwork {
+    pub name: String,
+    pub gamemode: String,
+    pub players: usize,
+    pub max_players: usize,
+    /// see `Gamemode::status`
+    pub status: String,
+}
+
+/// A tank, shape or bullet as seen by a client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectOverNetwork {
+    pub id: u128,
+    pub x: f64,
+    pub y: f64,
+    pub xvel: f64,
+    pub yvel: f64,
+    pub rot: f64,
+    pub collision_size: f64,
+    pub hp: f64,
+    pub max_hp: f64,
+    pub texture: String,
+    /// 0 for shapes and objects without a team
+    pub team: u8,
+    /// (position, direction in degrees) of the auto turrets of a tank, which turn on their own and are not part of the texture
+    #[serde(default)]
+    pub auto_turrets: Vec<((f64, f64), f64)>,
+    /// 1 when fully visible, 0 when faded out, see `Tank::visibility`. Always 1 for shapes and bullets
+    #[serde(default = "default_visibility")]
+    pub visibility: f64,
+}
+
+fn default_visibility() -> f64 {
+    1.
+}
+
+/// The battle royale zone of the room, see `Zone`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZoneOverNetwork {
+    pub center: (f64, f64),
+    pub radius: f64,
+    /// where the zone is moving to, None after the last phase
+    pub next: Option<((f64, f64), f64)>,
+    /// hp per second taken outside the zone
+    pub damage: f64,
+}
+
+/// Sent by the server to every client once per tick. Only contains the objects near the tank of the client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotOverNetwork {
+    /// increases by one every server tick, used by the client to throw away old or duplicated snapshots
+    pub tick: u64,
+    /// id of the tank controlled by the client receiving this snapshot
+    pub your_id: u128,
+    /// name of the room the client is in, empty if it is in none yet
+    pub room: String,
+    /// all the rooms on the server
+    pub rooms: Vec<RoomOverNetwork>,
+    pub tanks: Vec<ObjectOverNetwork>,
+    pub shapes: Vec<ObjectOverNetwork>,
+    pub bullets: Vec<ObjectOverNetwork>,
+    /// None if the room has no zone
+    pub zone: Option<ZoneOverNetwork>,
+    /// messages for everyone in the room since the last snapshot, like a boss spawning
+    pub announcements: Vec<String>,
+    /// static walls near the tank of the client
+    pub walls: Vec<Wall>,
+    /// laser shots near the tank of the client
+    #[serde(default)]
+    pub beams: Vec<Beam>,
 }
 
+impl SnapshotOverNetwork {
+    /// Sent to clients that are in no room or whose tank is dead
+    pub fn empty(tick: u64, your_id: u128) -> Self {
+        SnapshotOverNetwork {
+            tick,
+            your_id,
+            room: String::new(),
+            rooms: vec![],
+            tanks: vec![],
+            shapes: vec![],
+            bullets: vec![],
+            zone: None,
+            announcements: vec![],
+            walls: vec![],
+            beams: vec![],
+        }
+    }
+}
 
+/// Shared between the game loop and the threads of the connections
+#[derive(Default)]
+pub struct NetworkState {
+    /// latest received input of every connected client, by the id of the tank the client controls
+    pub inputs: HashMap<u128, InputOverNetwork>,
+    /// snapshots sent here are written to the client, by the id of the tank the client controls
+    pub outboxes: HashMap<u128, mpsc::Sender<String>>,
+    /// rooms requested by clients since the last frame, by the id of the tank the client controls
+    pub joins: HashMap<u128, String>,
+}
+
+/// Removes the first complete JSON object from the start of `data` and returns it. Returns None if there is no complete object yet.
+///
+/// Messages are not separated by anything, a message ends where its outermost bracket closes. Anything before the first '{' is thrown away.
+pub fn take_message(data: &mut Vec<u8>) -> Option<String> {
+    let start = data.iter().position(|c| *c == b'{');
+    let start = match start {
+        Some(start) => start,
+        None => {
+            data.clear();
+            return None
+        }
+    };
+
+    let mut inside_how_many_brackets = 0;
+    let mut inside_string = false;
+    let mut escaped = false;
+    for i in start..data.len() {
+        let c = data[i];
+        if inside_string {
+            if escaped {
+                escaped = false;
+            } else if c == b'\\' {
+                escaped = true;
+            } else if c == b'"' {
+                inside_string = false;
+            }
+            continue
+        }
+        if c == b'"' {
+            inside_string = true;
+        }
+        if c == b'{' {
+            inside_how_many_brackets += 1;
+        }
+        if c == b'}' {
+            inside_how_many_brackets -= 1;
+            if inside_how_many_brackets == 0 {
+                let message = String::from_utf8_lossy(&data[start..=i]).to_string();
+                data.drain(..=i);
+                return Some(message)
+            }
+        }
+    }
+    None
+}
+
+/// Makes the snapshot that is sent to the client controlling the tank `viewer_id`
+pub fn make_snapshot(map: &Map, tick: u64, viewer_id: u128) -> SnapshotOverNetwork {
+    let mut snapshot = SnapshotOverNetwork::empty(tick, viewer_id);
+    snapshot.zone = map.zone.as_ref().map(|zone| ZoneOverNetwork {
+        center: zone.center,
+        radius: zone.radius,
+        next: zone.next(),
+        damage: zone.damage(),
+    });
+
+    // the tank is dead, the client only needs to know the tick
+    let viewer_tank = match map.tanks.get(&viewer_id) {
+        Some(viewer) => viewer,
+        None => return snapshot,
+    };
+    let viewer = viewer_tank.physics;
+
+    // faded out enemies are left out, so clients can't see them either
+    for (id, tank) in map.tanks.iter().filter(|(_, t)| t.physics.dist(&viewer) < SNAPSHOT_RADIUS && (!t.hidden_at(&viewer) || t.is_teammate(viewer_tank))) {
+        let mut object = object_over_network(*id, &tank.physics, &tank.texture, tank.team);
+        object.visibility = tank.visibility;
+        object.auto_turrets = tank.turrets.iter().filter(|t| t.auto).map(|t| (t.mount(&tank.physics), t.direction(&tank.physics))).collect();
+        snapshot.tanks.push(object);
+    }
+    for (id, shape) in map.shapes.iter().filter(|(_, s)| s.physics.dist(&viewer) < SNAPSHOT_RADIUS) {
+        snapshot.shapes.push(object_over_network(*id, &shape.physics, &shape.texture, 0));
+    }
+    for (id, bullet) in map.bullets.iter().filter(|(_, b)| b.physics.dist(&viewer) < SNAPSHOT_RADIUS) {
+        snapshot.bullets.push(object_over_network(*id, &bullet.physics, &bullet.texture, bullet.team));
+    }
+    for wall in map.walls.iter() {
+        let closest = wall.closest_point((viewer.x, viewer.y));
+        if (closest.0 - viewer.x).powi(2) + (closest.1 - viewer.y).powi(2) < SNAPSHOT_RADIUS.powi(2) {
+            snapshot.walls.push(wall.clone());
+        }
+    }
+    for beam in map.beams.iter() {
+        if [beam.from, beam.to].iter().any(|p| (p.0 - viewer.x).powi(2) + (p.1 - viewer.y).powi(2) < SNAPSHOT_RADIUS.powi(2)) {
+            snapshot.beams.push(beam.clone());
+        }
+    }
+    snapshot
+}
+
+fn object_over_network(id: u128, physics: &Physics, texture: &str, team: u8) -> ObjectOverNetwork {
+    ObjectOverNetwork {
+        id,
+        x: physics.x,
+        y: physics.y,
+        xvel: physics.xvel,
+        yvel: physics.yvel,
+        rot: physics.rot,
+        collision_size: physics.collision_size,
+        hp: physics.hp,
+        max_hp: physics.max_hp,
+        texture: texture.to_owned(),
+        team,
+        auto_turrets: vec![],
+        visibility: 1.,
+    }
+}
+
+/// The game loop side of the network. Puts new connections into rooms, spawns their tanks, controls them by their inputs, and sends snapshots back
+pub struct NetworkServer {
+    pub state: Arc<Mutex<NetworkState>>,
+    /// index of the room of every network player, by the id of its tank
+    player_rooms: HashMap<u128, usize>,
+    tick: u64,
+}
+impl NetworkServer {
+    /// Starts accepting raw TCP connections on `address` in a new thread
+    pub fn start(address: &str) -> Self {
+        let state = Arc::new(Mutex::new(NetworkState::default()));
+        let address = address.to_owned();
+        let thread_state = state.clone();
+        thread::spawn(move || run_network(&address, thread_state, false));
+        NetworkServer {
+            state,
+            player_rooms: HashMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Also starts accepting WebSocket connections on `address`, in a new thread. They play in the same game as the TCP connections
+    pub fn listen_websocket(&self, address: &str) {
+        let address = address.to_owned();
+        let thread_state = self.state.clone();
+        thread::spawn(move || run_network(&address, thread_state, true));
+    }
+
+    /// Call once every frame, before the rooms are updated. Network players respawn the same way the local player does, as soon as their tank is missing
+    pub fn apply_inputs(&mut self, rooms: &mut [Room], delta: f64) {
+        let mut state = self.state.lock().unwrap();
+
+        // remove tanks of closed connections
+        let closed: Vec<u128> = self.player_rooms.keys().filter(|id| !state.outboxes.contains_key(id)).cloned().collect();
+        for id in closed {
+            rooms[self.player_rooms[&id]].remove_player(id);
+            self.player_rooms.remove(&id);
+        }
+
+        // move players to the rooms they asked for
+        for (id, requested) in state.joins.drain() {
+            // joining the room the player is already in would respawn them, and the player counts for it being full
+            if self.player_rooms.get(&id).is_some_and(|i| rooms[*i].settings.name == requested) {
+                continue
+            }
+            match Room::matchmake(rooms, Some(&requested)) {
+                Some(i) if rooms[i].settings.name == requested => {
+                    if let Some(old) = self.player_rooms.insert(id, i) {
+                        rooms[old].remove_player(id);
+                    }
+                    rooms[i].add_player(id);
+                }
+                _ => println!("Room {} does not exist or is full", requested),
+            }
+        }
+
+        for (id, input) in state.inputs.iter() {
+            if !self.player_rooms.contains_key(id) {
+                match Room::matchmake(rooms, None) {
+                    Some(i) => {
+                        self.player_rooms.insert(*id, i);
+                        rooms[i].add_player(*id);
+                    }
+                    // all rooms are full, the client only gets the list of rooms
+                    None => continue,
+                }
+            }
+
+            let room = &mut rooms[self.player_rooms[id]];
+            // the gamemode decides if and where the player respawns
+            if room.spawn_player(*id) && !room.gamemode.paused() {
+                let map = &mut room.map;
+                map.tanks.get_mut(id).unwrap().control(input, &mut map.bullets, *id, delta);
+            }
+        }
+    }
+
+    /// Call once every frame, after the rooms are updated
+    pub fn send_snapshots(&mut self, rooms: &[Room]) {
+        self.tick += 1;
+        let room_list: Vec<RoomOverNetwork> = rooms.iter().map(|r| RoomOverNetwork {
+            name: r.settings.name.clone(),
+            gamemode: r.gamemode.name().to_owned(),
+            players: r.players.len(),
+            max_players: r.settings.max_players,
+            status: r.gamemode.status(&r.map),
+        }).collect();
+
+        let state = self.state.lock().unwrap();
+        for (id, outbox) in state.outboxes.iter() {
+            let mut snapshot = match self.player_rooms.get(id) {
+                Some(i) => {
+                    let mut snapshot = make_snapshot(&rooms[*i].map, self.tick, *id);
+                    snapshot.room = rooms[*i].settings.name.clone();
+                    snapshot.announcements = rooms[*i].map.announcements.clone();
+                    snapshot
+                }
+                None => SnapshotOverNetwork::empty(self.tick, *id),
+            };
+            snapshot.rooms = room_list.clone();
+            // the receiver is dropped when the connection closes, it will be removed from outboxes by its thread
+            let _ = outbox.send(serde_json::to_string(&snapshot).unwrap());
+        }
+    }
+}
+
+/// One connected client, over raw TCP or over WebSocket. Both carry the same JSON messages
+pub trait Connection {
+    /// Returns the messages that arrived since the last call, waits at most a few milliseconds. Err if the connection closed
+    fn receive(&mut self) -> Result<Vec<String>, String>;
+    fn send(&mut self, message: &str) -> Result<(), String>;
+    fn close(&mut self);
+}
+
+/// Messages are JSON objects written one after another to the stream, see `take_message`
+pub struct TcpConnection {
+    stream: TcpStream,
+    data: Vec<u8>,
+}
+impl TcpConnection {
+    pub fn new(stream: TcpStream) -> Self {
+        // short timeout, so the same thread can both read and write
+        stream.set_read_timeout(Some(Duration::from_millis(2))).unwrap();
+        TcpConnection {
+            stream,
+            data: Vec::new(),
+        }
+    }
+}
+impl Connection for TcpConnection {
+    fn receive(&mut self) -> Result<Vec<String>, String> {
+        let mut buffer = [0; 65536];
+        match self.stream.read(&mut buffer) {
+            Ok(0) => return Err("connection closed".to_owned()),
+            Ok(bytes_read) => self.data.extend_from_slice(&buffer[..bytes_read]),
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {},
+            Err(e) => return Err(e.to_string()),
+        }
+
+        let mut messages = vec![];
+        while let Some(message) = take_message(&mut self.data) {
+            messages.push(message);
+        }
+        Ok(messages)
+    }
+
+    fn send(&mut self, message: &str) -> Result<(), String> {
+        self.stream.write_all(message.as_bytes()).map_err(|e| e.to_string())
+    }
+
+    fn close(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// Every text message is one JSON message, so browsers can send `JSON.stringify(input)` as it is
+pub struct WebSocketConnection {
+    socket: WebSocket<TcpStream>,
+}
+impl WebSocketConnection {
+    /// Does the server side of the WebSocket handshake
+    pub fn accept(stream: TcpStream) -> Result<Self, String> {
+        let socket = tungstenite::accept(stream).map_err(|e| e.to_string())?;
+        socket.get_ref().set_read_timeout(Some(Duration::from_millis(2))).unwrap();
+        Ok(WebSocketConnection { socket })
+    }
+
+    /// Does the client side of the WebSocket handshake, used by test clients. `address` is without the `ws://`
+    pub fn connect(address: &str) -> Result<Self, String> {
+        let stream = TcpStream::connect(address).map_err(|e| e.to_string())?;
+        let (socket, _) = tungstenite::client(format!("ws://{}/", address), stream).map_err(|e| e.to_string())?;
+        socket.get_ref().set_read_timeout(Some(Duration::from_millis(2))).unwrap();
+        Ok(WebSocketConnection { socket })
+    }
+}
+impl Connection for WebSocketConnection {
+    fn receive(&mut self) -> Result<Vec<String>, String> {
+        let mut messages = vec![];
+        loop {
+            match self.socket.read() {
+                Ok(Message::Text(text)) => messages.push(text.to_string()),
+                Ok(Message::Binary(data)) => messages.push(String::from_utf8_lossy(&data).to_string()),
+                Ok(Message::Close(_)) => return Err("connection closed".to_owned()),
+                // pings are answered by tungstenite
+                Ok(_) => {},
+                Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => break,
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        Ok(messages)
+    }
+
+    fn send(&mut self, message: &str) -> Result<(), String> {
+        self.socket.send(Message::text(message)).map_err(|e| e.to_string())
+    }
+
+    fn close(&mut self) {
+        let _ = self.socket.close(None);
+        let _ = self.socket.flush();
+    }
+}
 
 // spawned for each connection once, connection ends when the function finishes
-fn handle_client(mut stream: TcpStream) {
+fn handle_client(mut connection: impl Connection, state: Arc<Mutex<NetworkState>>) {
+    // the id of the connection is also the id of the tank it controls
     let connection_uuid: u128 = thread_rng().gen();
-    let mut buffer = [0; 1024];
-    let mut data = Vec::new();
-
-    loop {
-        let bytes_read = stream.read(&mut buffer).unwrap();
-        data.extend_from_slice(&buffer[..bytes_read]);
-    
-        // Trim the data, and check if the data contains a complete JSON message
-        let data_string = String::from_utf8(data.clone()).unwrap();
-        let mut inside_how_many_brackets = 0;
-        let mut trimmed_data_json = "".to_string();
-        for c in data_string.chars() {
-            if c == '{' {
-                inside_how_many_brackets += 1;
-            }
-            if c == '}' {
-                inside_how_many_brackets -= 1;
+
+    let (outbox, snapshots) = mpsc::channel::<String>();
+    state.lock().unwrap().outboxes.insert(connection_uuid, outbox);
+
+    'connection: loop {
+        let messages = match connection.receive() {
+            Ok(messages) => messages,
+            Err(e) => {
+                println!("Connection error: {}", e);
+                break 'connection
             }
-            trimmed_data_json.push(c);
-            if inside_how_many_brackets == 0 {
-                break
+        };
+
+        // only the newest input matters
+        for message in messages {
+            if let Ok(received_data) = serde_json::from_str::<InputOverNetwork>(&message) {
+                state.lock().unwrap().inputs.insert(connection_uuid, received_data);
+            } else if let Ok(join) = serde_json::from_str::<JoinOverNetwork>(&message) {
+                state.lock().unwrap().joins.insert(connection_uuid, join.join);
+            } else {
+                println!("data: {:?}", message);
+                println!("error: not an input nor a join message");
             }
         }
-        let received_data = serde_json::from_str::<InputOverNetwork>(&trimmed_data_json);
-        if received_data.is_ok() {
-            let received_data = received_data.unwrap();
-            println!("Received data: {:?}", received_data);
-
-            // get the system temp dir path
-            let temp_dir = std::env::temp_dir();
-            // all paths of received data begin with "recv"
-            let file_path = temp_dir.join(format!{"recv{}", connection_uuid});
-
-            // Write received data to a file.
-            println!("writing received data to file: {:?}", file_path);
-            let mut file = File::create(&file_path).unwrap();
-            file.write_all(format!{"{:?}", trimmed_data_json}.as_bytes()).unwrap();
-    
-            // Clear the data buffer for the next message
-            data.clear();
-            buffer = [0; 1024];
-        } else {
-            println!("data: {:?}", trimmed_data_json);
-            println!("error: {:?}", received_data);
+
+        for snapshot in snapshots.try_iter() {
+            if connection.send(&snapshot).is_err() {
+                break 'connection
+            }
         }
     }
+
+    let mut state = state.lock().unwrap();
+    state.inputs.remove(&connection_uuid);
+    state.outboxes.remove(&connection_uuid);
+    state.joins.remove(&connection_uuid);
+    connection.close();
+    println!("Connection closed");
 }
 
-pub fn run_network() {
-    let listener = TcpListener::bind("127.0.0.1:8080").unwrap();
+/// Accepts connections and handles each in its own thread. The game loop reads the inputs and sends the snapshots through `state`
+///
+/// With `websocket` set, the connections start with a WebSocket handshake, for browser clients
+pub fn run_network(address: &str, state: Arc<Mutex<NetworkState>>, websocket: bool) {
+    let listener = TcpListener::bind(address).unwrap();
     // accept connections and process them, spawning a new thread for each one
-    println!("Server listening on port 8080");
+    println!("{} listening on {}", if websocket {"WebSocket server"} else {"Server"}, address);
     for stream in listener.incoming() {
         match stream {
             Ok(stream) => {
                 println!("New connection: {}", stream.peer_addr().unwrap());
+                let state = state.clone();
                 thread::spawn(move|| {
                     // connection succeeded
-                    handle_client(stream)
+                    if websocket {
+                        // the handshake is done in the connection thread, so a slow client does not block others
+                        match WebSocketConnection::accept(stream) {
+                            Ok(connection) => handle_client(connection, state),
+                            Err(e) => println!("WebSocket handshake failed: {}", e),
+                        }
+                    } else {
+                        handle_client(TcpConnection::new(stream), state)
+                    }
                 });
             }
             Err(e) => {
@@ -0,0 +1,193 @@
+use std::collections::HashSet;
+
+use crate::gamemode::{self, Gamemode};
+use crate::zone::{Zone, ZonePhase};
+use crate::tank_tree::ROOT_CLASS;
+use crate::Map;
+
+/// Everything needed to create a room, see `Room`
+#[derive(Clone, Debug)]
+pub struct RoomSettings {
+    /// clients ask for a room by its name
+    pub name: String,
+    /// name of the gamemode, see `gamemode::from_name`
+    pub gamemode: String,
+    pub map_size: (f64, f64),
+    /// the map is filled with AI tanks up to this number of tanks, players included. The gamemode decides when
+    pub bots: usize,
+    /// players that do not fit are matched to another room
+    pub max_players: usize,
+    /// set to false to have a map with only tanks on it
+    pub shapes: bool,
+    /// schedule of the battle royale zone, None for no zone
+    pub zone: Option<Vec<ZonePhase>>,
+    /// seconds between boss spawns, counted from the death of the last boss. None for no bosses
+    pub boss_interval: Option<f64>,
+    /// seed of the map, see `Map::seed`. None for a random one
+    pub seed: Option<u64>,
+}
+
+/// One arena. Has its own `Map`, gamemode, bots and players, so one server can host for example an FFA and a Survival room at once.
+///
+/// A room without players sleeps, meaning it is not updated at all, and wakes up when a player joins.
+pub struct Room {
+    pub settings: RoomSettings,
+    pub map: Map,
+    pub gamemode: Box<dyn Gamemode>,
+    /// ids of the tanks controlled by players, local or network
+    pub players: HashSet<u128>,
+    pub asleep: bool,
+    /// the winner is only announced once
+    announced_winner: Option<String>,
+    /// seconds since there was a boss on the map
+    boss_timer: f64,
+}
+impl Room {
+    /// Panics if the gamemode in the settings does not exist
+    pub fn new(settings: RoomSettings) -> Self {
+        let mut gamemode = gamemode::from_name(&settings.gamemode)
+            .unwrap_or_else(|| panic!("unknown gamemode {}, available gamemodes: {:?}", settings.gamemode, gamemode::GAMEMODES));
+        let mut map = Map::new(settings.map_size);
+        map.zone = settings.zone.clone().map(|schedule| Zone::new(schedule, settings.map_size));
+        if let Some(seed) = settings.seed {
+            map.seed = seed;
+        }
+        gamemode.init(&mut map);
+        Room {
+            map,
+            settings,
+            gamemode,
+            players: HashSet::new(),
+            asleep: true,
+            announced_winner: None,
+            boss_timer: 0.,
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.players.len() >= self.settings.max_players
+    }
+
+    /// Only registers the player, the tank is spawned by `spawn_player`
+    pub fn add_player(&mut self, id: u128) {
+        self.players.insert(id);
+        self.asleep = false;
+    }
+
+    /// Unregisters the player and removes its tank
+    pub fn remove_player(&mut self, id: u128) {
+        self.players.remove(&id);
+        self.map.tanks.remove(&id);
+    }
+
+    /// Gives the player a new tank if it has none and the gamemode allows it. Returns true if the player has a tank
+    pub fn spawn_player(&mut self, id: u128) -> bool {
+        if !self.map.tanks.contains_key(&id) {
+            if !self.gamemode.can_respawn(&self.map, id) {
+                return false
+            }
+            self.map.spawn_tank(id, ROOT_CLASS);
+            self.place(id);
+        }
+        true
+    }
+
+    /// Puts a just spawned tank into its team and moves it to where the gamemode wants it
+    fn place(&mut self, id: u128) {
+        let team = self.gamemode.team_for(&self.map, id);
+        self.map.tanks.get_mut(&id).unwrap().team = team;
+        let (x, y) = self.gamemode.spawn_position(&self.map, id);
+        let ph = &mut self.map.tanks.get_mut(&id).unwrap().physics;
+        ph.x = x;
+        ph.y = y;
+    }
+
+    /// Picks a room for a new player. The requested room if it exists and is not full, otherwise the fullest room that still has space, so players end up together.
+    ///
+    /// Returns the index of the room in `rooms`, None if all rooms are full
+    pub fn matchmake(rooms: &[Room], requested: Option<&str>) -> Option<usize> {
+        if let Some(requested) = requested {
+            if let Some(i) = rooms.iter().position(|r| r.settings.name == requested && !r.is_full()) {
+                return Some(i)
+            }
+        }
+        rooms.iter().enumerate()
+            .filter(|(_, r)| !r.is_full())
+            .max_by_key(|(_, r)| r.players.len())
+            .map(|(i, _)| i)
+    }
+
+    /// Call once every frame, after the players are controlled. Spawns bots, controls AI tanks, updates physics, and calls the gamemode hooks.
+    ///
+    /// Does nothing and falls asleep if the room has no players, and does nothing while the gamemode is paused.
+    pub fn update(&mut self, delta: f64) {
+        if self.players.is_empty() {
+            self.asleep = true;
+            return
+        }
+        self.asleep = false;
+        self.map.announcements.clear();
+        if self.gamemode.paused() {
+            return
+        }
+
+        // SPAWN TANKS
+
+        let bots = self.gamemode.bot_count(&self.map, self.settings.bots);
+        for id in self.map.spawn_bots(bots) {
+            self.place(id);
+        }
+
+        if let Some(interval) = self.settings.boss_interval {
+            if self.map.bossais.is_empty() {
+                self.boss_timer += delta;
+            }
+            if self.boss_timer >= interval {
+                self.boss_timer = 0.;
+                self.map.spawn_boss();
+            }
+        }
+
+        // AI CONTROL
+
+        // this will call all the AIs' control functions, and keep only the AIs that return true
+        let map = &mut self.map;
+        map.tankais.retain_mut(|ai |ai.control(&mut map.tanks, &mut map.shapes, &mut map.bullets, &map.walls, delta));
+        map.bossais.retain_mut(|ai| ai.control(&mut map.tanks, &mut map.bullets, &mut map.announcements, delta));
+        map.aim_auto_turrets(&*self.gamemode);
+        map.fire_lasers(&*self.gamemode, delta);
+
+        // PHYSICS
+
+        self.map.update_zone(delta);
+        self.map.update_physics(delta, &*self.gamemode);
+
+        // GAMEMODE
+
+        let deaths: Vec<(u128, Option<u128>)> = self.map.deaths.drain(..).collect();
+        for (victim, killer) in deaths {
+            if let Some(killer) = killer {
+                self.gamemode.on_kill(&mut self.map, killer, victim);
+            }
+            self.gamemode.on_tank_death(&mut self.map, victim);
+        }
+
+        self.gamemode.on_tick(&mut self.map, delta);
+
+        let winner = self.gamemode.winner(&self.map);
+        if let Some(w) = &winner {
+            if winner != self.announced_winner {
+                println!("Room {}: {} won", self.settings.name, w);
+            }
+        }
+        self.announced_winner = winner;
+
+        for announcement in &self.map.announcements {
+            println!("Room {}: {}", self.settings.name, announcement);
+        }
+
+        if self.settings.shapes {
+            self.map.shapes_max = ((self.map.map_size.0 * self.map.map_size.1) / 16384.) as usize;
+        }
+    }
+}
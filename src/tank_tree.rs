@@ -1,872 +1,439 @@
 extern crate lazy_static;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use lazy_static::lazy_static;
+use serde::Deserialize;
+use serde_json::{Map, Value};
 
-use crate::{Tank, Physics, Turret};
+use crate::{Tank, Physics, Turret, Evolution};
+
+/// Directory the class definitions are loaded from, every `.json` file in it is read
+pub const CLASSES_DIR: &str = "classes";
+
+/// Class name -> (default tank of the class, classes it evolves to, cost), see `EVOLUTION_TREE`
+pub type ClassTree = HashMap<String, (Tank, Vec<String>, f64)>;
 
 /// init
 lazy_static! {
     /// Vec<> of all the tank classes.
-    /// 
+    ///
     /// Contains data in the following format: (`DefaultTank`, `EvolveTo`, `Cost`)
-    /// 
+    ///
     /// `DefaultTank` is the default values of the tank of this class, all at level 1.
     /// `EvolveTo` is a vec of names of all the classes a tank can evolve to from this class.
     /// `Cost` is how much it costs to evolve to (not from) the particular class.
-    /// 
-    pub static ref EVOLUTION_TREE: HashMap<String, (Tank, Vec<String>, f64)> = {
-        let mut hash_set = HashMap::new();
-
-        hash_set.insert("basic".to_string(), (
-            Tank {
-                physics: Physics {
-                    x: 0.,
-                    y: 0.,
-                    xvel: 0.,
-                    yvel: 0.,
-                    weight: 20.,
-                    rot: 0.,
-                    rotvel: 0.,
-                    collision_size: 30.,
-                    hp: 20.,
-                    max_hp: 20.,
-                    hp_regen: 1.,
-                },
-                turrets: vec![Turret {
-                    projectile_impulse: 2_000.,
-                    projectile_weight: 3.,
-                    projectile_collision_size: 12.,
-                    projectile_hp_regen: -1.5,
-                    projectile_hp: 3.,
-                    reload_time: 1.,
-                    inaccuracy: 1.,
-                    relative_position: (0.,-52.),
-                    ..Default::default()
-                }],
-                power: 3000.,
-                rot_power: 50.,
-                texture: "basic".to_owned(),
-                ..Default::default()
-            }, vec![
-                "double".to_string(),
-                "sniper".to_string(),
-                "bomber".to_string(),
-                "trapper".to_string(),
-                "spawner".to_string(),],
-            1000.
-        ));
-
-
-        {
-            hash_set.insert("double".to_string(), (
-                Tank {
-                    physics: Physics {
-                        x: 0.,
-                        y: 0.,
-                        xvel: 0.,
-                        yvel: 0.,
-                        weight: 25.,
-                        rot: 0.,
-                        rotvel: 0.,
-                        collision_size: 40.,
-                        hp: 25.,
-                        max_hp: 25.,
-                        hp_regen: 1.1,
-                    },
-                    turrets: vec![
-                    Turret {
-                        projectile_impulse: 1300.,
-                        projectile_weight: 2.15,
-                        projectile_collision_size: 11.,
-                        projectile_hp_regen: -1.1,
-                        projectile_hp: 2.15,
-                        reload_time: 1.,
-                        inaccuracy: 2.,
-                        relative_position: (-20.,-50.),
-                        ..Default::default()
-                    },
-                    Turret {
-                        projectile_impulse: 1300.,
-                        projectile_weight: 2.15,
-                        projectile_collision_size: 11.,
-                        projectile_hp_regen: -1.1,
-                        projectile_hp: 2.15,
-                        reload_time: 1.,
-                        inaccuracy: 2.,
-                        relative_position: (20.,-50.),
-                        ..Default::default()
-                    },],
-                    power: 3000.,
-                    rot_power: 50.,
-                    texture: "double".to_owned(),
-                    ..Default::default()                
-                }, vec![
-                    "hailstorm".to_string(),
-                    "triple".to_string(),
-                    "cross".to_string()
-                ],
-                1000.
-            ));
-
-
-            hash_set.insert("triple".to_string(), (
-                Tank {
-                    physics: Physics {
-                        x: 0.,
-                        y: 0.,
-                        xvel: 0.,
-                        yvel: 0.,
-                        weight: 30.,
-                        rot: 0.,
-                        rotvel: 0.,
-                        collision_size: 50.,
-                        hp: 30.,
-                        max_hp: 30.,
-                        hp_regen: 1.2,
-                    },
-                    turrets: vec![
-                    Turret {
-                        projectile_impulse: 1200.,
-                        projectile_weight: 2.,
-                        projectile_collision_size: 10.,
-                        projectile_hp_regen: -1.1,
-                        projectile_hp: 2.,
-                        reload_time: 1.,
-                        inaccuracy: 1.,
-                        relative_position: (-25.,-55.),
-                        ..Default::default()
-                    },
-                    Turret {
-                        projectile_impulse: 1200.,
-                        projectile_weight: 2.,
-                        projectile_collision_size: 10.,
-                        projectile_hp_regen: -1.1,
-                        projectile_hp: 2.,
-                        reload_time: 1.,
-                        inaccuracy: 1.,
-                        relative_position: (0.,-65.),
-                        ..Default::default()
-                    },
-                    Turret {
-                        projectile_impulse: 1200.,
-                        projectile_weight: 2.,
-                        projectile_collision_size: 10.,
-                        projectile_hp_regen: -1.1,
-                        projectile_hp: 2.,
-                        reload_time: 1.,
-                        inaccuracy: 1.,
-                        relative_position: (25.,-55.),
-                        ..Default::default()
-                    },],
-                    power: 3000.,
-                    rot_power: 50.,
-                    texture: "triple".to_owned(),
-                    ..Default::default()                
-                }, vec![
-                ],
-                1000.
-            ));
-
-
-            hash_set.insert("cross".to_string(), (
-                Tank {
-                    physics: Physics {
-                        x: 0.,
-                        y: 0.,
-                        xvel: 0.,
-                        yvel: 0.,
-                        weight: 80.,
-                        rot: 0.,
-                        rotvel: 0.,
-                        collision_size: 60.,
-                        hp: 80.,
-                        max_hp: 80.,
-                        hp_regen: 1.5,
-                    },
-                    turrets: vec![
-                        Turret {
-                            projectile_impulse: 2000.,
-                            projectile_weight: 3.,
-                            projectile_collision_size: 15.,
-                            projectile_hp_regen: -2.,
-                            projectile_hp: 5.,
-                            reload_time: 1.,
-                            inaccuracy: 1.,
-                            relative_position: (0.,0.),
-                            relative_direction: 0.,
-                            ..Default::default()
-                        },
-                        Turret {
-                            projectile_impulse: 2000.,
-                            projectile_weight: 3.,
-                            projectile_collision_size: 15.,
-                            projectile_hp_regen: -2.,
-                            projectile_hp: 5.,
-                            reload_time: 1.,
-                            inaccuracy: 1.,
-                            relative_position: (0.,0.),
-                            relative_direction: 180.,
-                            ..Default::default()
-                        },
-                        Turret {
-                            projectile_impulse: 2000.,
-                            projectile_weight: 3.,
-                            projectile_collision_size: 15.,
-                            projectile_hp_regen: -2.,
-                            projectile_hp: 5.,
-                            reload_time: 1.,
-                            inaccuracy: 1.,
-                            relative_position: (0.,0.),
-                            relative_direction: 90.,
-                            ..Default::default()
-                        },
-                        Turret {
-                            projectile_impulse: 2000.,
-                            projectile_weight: 3.,
-                            projectile_collision_size: 15.,
-                            projectile_hp_regen: -2.,
-                            projectile_hp: 5.,
-                            reload_time: 1.,
-                            inaccuracy: 1.,
-                            relative_position: (0.,0.),
-                            relative_direction: 270.,
-                            ..Default::default()
-                        },
-                    ],
-                    power: 4500.,
-                    rot_power: 50.,
-                    texture: "cross".to_owned(),
-                    ..Default::default()                
-                }, vec![
-                ],
-                1000.
-            ));
-
-
-            hash_set.insert("hailstorm".to_string(), (
-                Tank {
-                    physics: Physics {
-                        x: 0.,
-                        y: 0.,
-                        xvel: 0.,
-                        yvel: 0.,
-                        weight: 100.,
-                        rot: 0.,
-                        rotvel: 0.,
-                        collision_size: 60.,
-                        hp: 100.,
-                        max_hp: 100.,
-                        hp_regen: 3.,
-                    },
-                    turrets: vec![Turret {
-                        projectile_impulse: 2000.,
-                        projectile_weight: 2.,
-                        projectile_collision_size: 10.,
-                        projectile_hp_regen: -2.,
-                        projectile_hp: 3.,
-                        reload_time: 0.2,
-                        inaccuracy: 5.,
-                        relative_position: (0.,-85.),
-                        ..Default::default()
-                    }],
-                    power: 4000.,
-                    rot_power: 50.,
-                    texture: "hailstorm".to_owned(),
-                    ..Default::default()                
-                }, vec![
-    
-                ],
-                1000.
-            ));
+    ///
+    /// Loaded from `CLASSES_DIR` on first use, see `load_classes`
+    pub static ref EVOLUTION_TREE: ClassTree = match load_classes(CLASSES_DIR) {
+        Ok(tree) => tree,
+        Err(e) => panic!("could not load the tank classes from {}/: {}", CLASSES_DIR, e),
+    };
+}
+
+/// Hints for `TankAI` about how to play a class, for things it can't figure out from the turrets
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AiHints {
+    /// distance the AI tries to keep from its target. Computed from the first turret's bullet range if not set
+    #[serde(default)]
+    pub target_range: Option<f64>,
+    /// drives into enemies and shapes instead of keeping its distance and dodging them, for classes with high `body_damage`
+    #[serde(default)]
+    pub rams: bool,
+}
+
+/// One class in a definition file, after its `extends` chain has been merged in
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClassDef {
+    /// defaults to the class name
+    texture: Option<String>,
+    cost: f64,
+    #[serde(default)]
+    evolve_to: Vec<String>,
+    weight: f64,
+    collision_size: f64,
+    hp: f64,
+    hp_regen: f64,
+    power: f64,
+    rot_power: f64,
+    #[serde(default = "default_body_damage")]
+    body_damage: f64,
+    #[serde(default)]
+    knockback_resistance: f64,
+    /// no limit by default
+    #[serde(default)]
+    max_drones: Option<usize>,
+    #[serde(default)]
+    infects: bool,
+    /// 0 for classes that never fade out
+    #[serde(default)]
+    stealth: f64,
+    /// fields shared by all the turrets of this class, each turret can override them
+    #[serde(default)]
+    turret: Map<String, Value>,
+    #[serde(default)]
+    turrets: Vec<Map<String, Value>>,
+    /// turrets that aim on their own, added after `turrets`. Kept separate so a class can add them to the turrets it `extends`
+    #[serde(default)]
+    auto_turrets: Vec<Map<String, Value>>,
+    /// turrets placed procedurally, added after `auto_turrets`
+    #[serde(default)]
+    generate: Vec<TurretGenerator>,
+    #[serde(default)]
+    ai: AiHints,
+}
+
+fn default_body_damage() -> f64 {
+    1.
+}
+
+/// Spreads `count` turrets evenly between two directions
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TurretGenerator {
+    count: usize,
+    /// first and last direction in degrees, relative to the tank facing
+    directions: (f64, f64),
+    /// if set, each turret is moved this far from the tank center in its direction.
+    /// Otherwise the position from `turret` is used as it is
+    #[serde(default)]
+    distance: Option<f64>,
+    #[serde(default)]
+    turret: Map<String, Value>,
+}
+
+/// Reads every `.json` file in `dir` into the `EVOLUTION_TREE` format.
+///
+/// Each file is an object of class name -> definition. A definition can `extends` another class or template (from any file),
+/// whose fields it inherits. Objects are merged field by field, anything else (like lists) is replaced.
+/// Definitions with `"template": true` are only used for inheriting and are not classes themselves.
+pub fn load_classes(dir: &str) -> Result<ClassTree, String> {
+    let definitions = read_definitions(dir)?;
+
+    let mut tree = HashMap::new();
+    for name in definitions.keys() {
+        let mut resolved = resolve(name, &definitions, &mut vec![])?;
+        // templates can leave out anything, they are only checked through the classes using them
+        match resolved.remove("template") {
+            Some(Value::Bool(true)) => continue,
+            None | Some(Value::Bool(false)) => {},
+            Some(_) => return Err(format!("class \"{}\": template has to be true or false", name)),
         }
+        let def: ClassDef = serde_json::from_value(Value::Object(resolved)).map_err(|e| format!("class \"{}\": {}", name, e))?;
+        let class = build(name, def).map_err(|e| format!("class \"{}\": {}", name, e))?;
+        tree.insert(name.clone(), class);
+    }
+    if tree.is_empty() {
+        return Err("no classes defined".to_owned())
+    }
+    Ok(tree)
+}
 
+/// All the definitions from all the files in `dir`, unresolved
+fn read_definitions(dir: &str) -> Result<HashMap<String, Map<String, Value>>, String> {
+    let mut paths = vec![];
+    for entry in std::fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().is_some_and(|e| e == "json") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
 
-        {
-            hash_set.insert("spawner".to_string(), (
-                Tank {
-                    physics: Physics {
-                        x: 0.,
-                        y: 0.,
-                        xvel: 0.,
-                        yvel: 0.,
-                        weight: 25.,
-                        rot: 0.,
-                        rotvel: 0.,
-                        collision_size: 40.,
-                        hp: 25.,
-                        max_hp: 25.,
-                        hp_regen: 1.,
-                    },
-                    turrets: vec![Turret {
-                        projectile_impulse: 2_000.,
-                        projectile_weight: 6.,
-                        projectile_collision_size: 15.,
-                        projectile_hp_regen: -1.2,
-                        projectile_hp: 12.,
-                        reload_time: 2.,
-                        inaccuracy: 1.,
-                        relative_position: (0.,-52.),
-                        projectile_texture: "drone".to_string(),
-                        ..Default::default()
-                    }],
-                    power: 3000.,
-                    rot_power: 50.,
-                    texture: "spawner".to_owned(),
-                    ..Default::default()
-                }, vec![
-                    "infector".to_owned(),
-                    "anthill".to_owned(),
-                    "trapspawner".to_owned(),
-                ],
-                1000.
-            ));
-
-
-            hash_set.insert("anthill".to_string(), (
-                Tank {
-                    physics: Physics {
-                        x: 0.,
-                        y: 0.,
-                        xvel: 0.,
-                        yvel: 0.,
-                        weight: 35.,
-                        rot: 0.,
-                        rotvel: 0.,
-                        collision_size: 50.,
-                        hp: 35.,
-                        max_hp: 35.,
-                        hp_regen: 1.,
-                    },
-                    turrets: vec![Turret {
-                        projectile_impulse: 1_000.,
-                        projectile_weight: 3.,
-                        projectile_collision_size: 10.,
-                        projectile_hp_regen: -0.5,
-                        projectile_hp: 5.,
-                        reload_time: 1.6,
-                        inaccuracy: 1.,
-                        relative_position: (0.,0.),
-                        relative_direction: 0.,
-                        projectile_texture: "drone".to_string(),
-                        ..Default::default()
-                    },
-                    Turret {
-                        projectile_impulse: 1_000.,
-                        projectile_weight: 3.,
-                        projectile_collision_size: 10.,
-                        projectile_hp_regen: -0.5,
-                        projectile_hp: 5.,
-                        reload_time: 1.6,
-                        inaccuracy: 1.,
-                        relative_position: (0.,0.),
-                        relative_direction: 120.,
-                        projectile_texture: "drone".to_string(),
-                        ..Default::default()
-                    },
-                    Turret {
-                        projectile_impulse: 1_000.,
-                        projectile_weight: 3.,
-                        projectile_collision_size: 10.,
-                        projectile_hp_regen: -0.5,
-                        projectile_hp: 5.,
-                        reload_time: 1.6,
-                        inaccuracy: 1.,
-                        relative_position: (0.,0.),
-                        relative_direction: -120.,
-                        projectile_texture: "drone".to_string(),
-                        ..Default::default()
-                    }],
-                    power: 2500.,
-                    rot_power: 40.,
-                    texture: "anthill".to_owned(),
-                    ..Default::default()
-                }, vec![
-                    "infector".to_owned(),
-                    "anthill".to_owned(),
-                ],
-                1000.
-            ));
-
-            hash_set.insert("infector".to_string(), (
-                Tank {
-                    physics: Physics {
-                        x: 0.,
-                        y: 0.,
-                        xvel: 0.,
-                        yvel: 0.,
-                        weight: 30.,
-                        rot: 0.,
-                        rotvel: 0.,
-                        collision_size: 45.,
-                        hp: 30.,
-                        max_hp: 30.,
-                        hp_regen: 1.,
-                    },
-                    turrets: vec![Turret {
-                        projectile_impulse: 2_000.,
-                        projectile_weight: 6.,
-                        projectile_collision_size: 15.,
-                        projectile_hp_regen: -1.2,
-                        projectile_hp: 12.,
-                        reload_time: 2.4,
-                        inaccuracy: 1.,
-                        relative_position: (0.,-52.),
-                        projectile_texture: "drone".to_string(),
-                        ..Default::default()
-                    }],
-                    power: 3500.,
-                    rot_power: 50.,
-                    texture: "infector".to_owned(),
-                    ..Default::default()
-                }, vec![
-                ],
-                1000.
-            ));
+    let mut definitions = HashMap::new();
+    for path in paths {
+        let data = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let file: Map<String, Value> = serde_json::from_str(&data).map_err(|e| format!("{}: {}", path.display(), e))?;
+        for (name, def) in file {
+            let Value::Object(def) = def else {
+                return Err(format!("{}: class \"{}\" is not an object", path.display(), name))
+            };
+            if definitions.insert(name.clone(), def).is_some() {
+                return Err(format!("{}: class \"{}\" is defined more than once", path.display(), name))
+            }
         }
+    }
+    Ok(definitions)
+}
 
+/// The definition of `name` with everything it `extends` merged in. `chain` is used to detect loops
+fn resolve(name: &str, definitions: &HashMap<String, Map<String, Value>>, chain: &mut Vec<String>) -> Result<Map<String, Value>, String> {
+    if chain.iter().any(|c| c == name) {
+        return Err(format!("\"{}\" extends itself: {} -> {}", name, chain.join(" -> "), name))
+    }
+    let Some(def) = definitions.get(name) else {
+        return Err(format!("\"{}\" extends \"{}\", which does not exist", chain.last().unwrap(), name))
+    };
+    let mut def = def.clone();
+    let Some(parent) = def.remove("extends") else {
+        return Ok(def)
+    };
+    let Value::String(parent) = parent else {
+        return Err(format!("class \"{}\": extends has to be a class name", name))
+    };
 
-        {
-            hash_set.insert("bomber".to_string(), (
-                Tank {
-                    physics: Physics {
-                        x: 0.,
-                        y: 0.,
-                        xvel: 0.,
-                        yvel: 0.,
-                        weight: 40.,
-                        rot: 0.,
-                        rotvel: 0.,
-                        collision_size: 50.,
-                        hp: 40.,
-                        max_hp: 40.,
-                        hp_regen: 1.,
-                    },
-                    turrets: vec![Turret {
-                        projectile_impulse: 3_000.,
-                        projectile_weight: 30.,
-                        projectile_collision_size: 32.,
-                        projectile_hp_regen: -5.,
-                        projectile_hp: 25.,
-                        reload_time: 7.,
-                        inaccuracy: 1.,
-                        relative_position: (0.,-70.),
-                        projectile_texture: "bomb".to_string(),
-                        ..Default::default()
-                    }],
-                    power: 4500.,
-                    rot_power: 80.,
-                    texture: "bomber".to_owned(),
-                    ..Default::default()                
-                }, vec![
-                    "trapbomber".to_string(),
-                    "tribomber".to_string(),
-                    "magnet bomber".to_string(),
-                ],
-                1000.
-            ));
-
-            hash_set.insert("magnet bomber".to_string(), (
-                Tank {
-                    physics: Physics {
-                        x: 0.,
-                        y: 0.,
-                        xvel: 0.,
-                        yvel: 0.,
-                        weight: 45.,
-                        rot: 0.,
-                        rotvel: 0.,
-                        collision_size: 50.,
-                        hp: 45.,
-                        max_hp: 45.,
-                        hp_regen: 1.1,
-                    },
-                    turrets: vec![Turret {
-                        projectile_impulse: 3_000.,
-                        projectile_weight: 30.,
-                        projectile_collision_size: 32.,
-                        projectile_hp_regen: -5.,
-                        projectile_hp: 25.,
-                        reload_time: 7.,
-                        inaccuracy: 1.,
-                        relative_position: (0.,-80.),
-                        projectile_texture: "mbomb".to_string(),
-                        ..Default::default()
-                    }],
-                    power: 4500.,
-                    rot_power: 80.,
-                    texture: "magnetbomber".to_owned(),
-                    ..Default::default()                
-                }, vec![
-                    "tribomber".to_string(),
-                    "trapbomber".to_string(),
-                ],
-                1000.
-            ));
-    
-            hash_set.insert("tribomber".to_string(), (
-                Tank {
-                    physics: Physics {
-                        x: 0.,
-                        y: 0.,
-                        xvel: 0.,
-                        yvel: 0.,
-                        weight: 50.,
-                        rot: 0.,
-                        rotvel: 0.,
-                        collision_size: 55.,
-                        hp: 50.,
-                        max_hp: 50.,
-                        hp_regen: 1.1,
-                    },
-                    turrets: vec![Turret {
-                        projectile_impulse: 5_000.,
-                        projectile_weight: 20.,
-                        projectile_collision_size: 28.,
-                        projectile_hp_regen: -3.,
-                        projectile_hp: 15.,
-                        reload_time: 9.,
-                        inaccuracy: 1.,
-                        relative_position: (-15.,-65.),
-                        projectile_texture: "bomb".to_string(),
-                        relative_direction: -10.,
-                        ..Default::default()
-                    },
-                    Turret {
-                        projectile_impulse: 5_000.,
-                        projectile_weight: 20.,
-                        projectile_collision_size: 28.,
-                        projectile_hp_regen: -3.,
-                        projectile_hp: 15.,
-                        reload_time: 9.,
-                        inaccuracy: 1.,
-                        relative_position: (0.,-70.),
-                        projectile_texture: "bomb".to_string(),
-                        relative_direction: 0.,
-                        ..Default::default()
-                    },
-                    Turret {
-                        projectile_impulse: 5_000.,
-                        projectile_weight: 20.,
-                        projectile_collision_size: 28.,
-                        projectile_hp_regen: -3.,
-                        projectile_hp: 15.,
-                        reload_time: 9.,
-                        inaccuracy: 1.,
-                        relative_position: (15.,-65.),
-                        projectile_texture: "bomb".to_string(),
-                        relative_direction: 10.,
-                        ..Default::default()
-                    }
-                    ],
-                    power: 4500.,
-                    rot_power: 80.,
-                    texture: "tribomber".to_owned(),
-                    ..Default::default()                
-                }, vec![
-                ],
-                1000.
-            ));
+    chain.push(name.to_owned());
+    let mut resolved = resolve(&parent, definitions, chain)?;
+    chain.pop();
+
+    // being a template is not inherited
+    resolved.remove("template");
+    merge(&mut resolved, def);
+    Ok(resolved)
+}
+
+/// Overrides the fields of `base` with the ones in `over`, merging objects recursively
+fn merge(base: &mut Map<String, Value>, over: Map<String, Value>) {
+    for (key, value) in over {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Object(base)), Value::Object(value)) => merge(base, value),
+            (_, value) => {base.insert(key, value);},
         }
+    }
+}
 
-        {
-            hash_set.insert("trapper".to_string(), (
-                Tank {
-                    physics: Physics {
-                        x: 0.,
-                        y: 0.,
-                        xvel: 0.,
-                        yvel: 0.,
-                        weight: 40.,
-                        rot: 0.,
-                        rotvel: 0.,
-                        collision_size: 45.,
-                        hp: 40.,
-                        max_hp: 40.,
-                        hp_regen: 1.,
-                    },
-                    turrets: vec![Turret {
-                        projectile_impulse: 20_000.,
-                        projectile_weight: 200.,
-                        projectile_collision_size: 6.,
-                        projectile_hp_regen: -8.,
-                        projectile_hp: 200.,
-                        reload_time: 1.2,
-                        inaccuracy: 0.,
-                        relative_position: (0.,-65.),
-                        projectile_texture: "trap".to_string(),
-                        ..Default::default()
-                    }],
-                    power: 3000.,
-                    rot_power: 40.,
-                    texture: "trapper".to_owned(),
-                    ..Default::default()                
-                }, vec![
-                    "trapbomber".to_string(),
-                    "trapspawner".to_string(),
-                    "barricade".to_string(),
-                    ],
-                1000.
-            ));
-
-            hash_set.insert("trapspawner".to_string(), (
-                Tank {
-                    physics: Physics {
-                        x: 0.,
-                        y: 0.,
-                        xvel: 0.,
-                        yvel: 0.,
-                        weight: 45.,
-                        rot: 0.,
-                        rotvel: 0.,
-                        collision_size: 55.,
-                        hp: 45.,
-                        max_hp: 45.,
-                        hp_regen: 1.1,
-                    },
-                    turrets: vec![Turret {
-                        projectile_impulse: 16_000.,
-                        projectile_weight: 50.,
-                        projectile_collision_size: 4.,
-                        projectile_hp_regen: -3.,
-                        projectile_hp: 50.,
-                        reload_time: 1.2,
-                        inaccuracy: 4.,
-                        relative_position: (0.,0.),
-                        relative_direction: -120.,
-                        projectile_texture: "trap".to_string(),
-                        ..Default::default()
-                    },
-                    Turret {
-                        projectile_impulse: 16_000.,
-                        projectile_weight: 50.,
-                        projectile_collision_size: 4.,
-                        projectile_hp_regen: -3.,
-                        projectile_hp: 50.,
-                        reload_time: 1.2,
-                        inaccuracy: 4.,
-                        relative_position: (0.,0.),
-                        relative_direction: 120.,
-                        projectile_texture: "trap".to_string(),
-                        ..Default::default()
-                    },
-                    // spawner turret
-                    Turret {
-                        projectile_impulse: 1_000.,
-                        projectile_weight: 3.,
-                        projectile_collision_size: 12.,
-                        projectile_hp_regen: -0.5,
-                        projectile_hp: 6.,
-                        reload_time: 1.8,
-                        inaccuracy: 1.,
-                        relative_position: (0.,-60.),
-                        projectile_texture: "drone".to_string(),
-                        ..Default::default()
-                    }],
-                    power: 3000.,
-                    rot_power: 40.,
-                    texture: "trapspawner".to_owned(),
-                    ..Default::default()                
-                }, vec![
-                    ],
-                1000.
-            ));
-
-            hash_set.insert("barricade".to_string(), (
-                Tank {
-                    physics: Physics {
-                        x: 0.,
-                        y: 0.,
-                        xvel: 0.,
-                        yvel: 0.,
-                        weight: 55.,
-                        rot: 0.,
-                        rotvel: 0.,
-                        collision_size: 60.,
-                        hp: 55.,
-                        max_hp: 55.,
-                        hp_regen: 1.2,
-                    },
-                    turrets: vec![Turret {
-                        projectile_impulse: 16_000.,
-                        projectile_weight: 50.,
-                        projectile_collision_size: 4.,
-                        projectile_hp_regen: -3.,
-                        projectile_hp: 50.,
-                        reload_time: 0.3,
-                        inaccuracy: 10.,
-                        relative_position: (0.,-65.),
-                        projectile_texture: "trap".to_string(),
-                        ..Default::default()
-                    }],
-                    power: 3000.,
-                    rot_power: 40.,
-                    texture: "barricade".to_owned(),
-                    ..Default::default()                
-                }, vec![
-                    ],
-                1000.
-            ));
-    
-
-            hash_set.insert("trapbomber".to_string(), (
-                Tank {
-                    physics: Physics {
-                        x: 0.,
-                        y: 0.,
-                        xvel: 0.,
-                        yvel: 0.,
-                        weight: 50.,
-                        rot: 0.,
-                        rotvel: 0.,
-                        collision_size: 50.,
-                        hp: 50.,
-                        max_hp: 50.,
-                        hp_regen: 1.2,
-                    },
-                    turrets: vec![Turret {
-                        projectile_impulse: 10_000.,
-                        projectile_weight: 50.,
-                        projectile_collision_size: 16.,
-                        projectile_hp_regen: -10.,
-                        projectile_hp: 50.,
-                        reload_time: 6.0,
-                        inaccuracy: 1.,
-                        relative_position: (0.,-80.),
-                        projectile_texture: "trapbomb".to_string(),
-                        ..Default::default()
-                    }],
-                    power: 3000.,
-                    rot_power: 40.,
-                    texture: "trapbomber".to_owned(),
-                    ..Default::default()                
-                }, vec![],
-                1000.
-            ));
+fn build(name: &str, def: ClassDef) -> Result<(Tank, Vec<String>, f64), String> {
+    let turret = |fields: Map<String, Value>| -> Result<Turret, String> {
+        let mut merged = def.turret.clone();
+        merge(&mut merged, fields);
+        serde_json::from_value(Value::Object(merged)).map_err(|e| format!("turret: {}", e))
+    };
+
+    let mut turrets = vec![];
+    for t in def.turrets {
+        turrets.push(turret(t)?);
+    }
+    for t in def.auto_turrets {
+        let mut t = turret(t)?;
+        t.auto = true;
+        turrets.push(t);
+    }
+    for generator in def.generate {
+        let (from, to) = generator.directions;
+        for i in 0..generator.count {
+            let mut t = turret(generator.turret.clone())?;
+            t.relative_direction = if generator.count > 1 {
+                from + (to - from) * i as f64 / (generator.count - 1) as f64
+            } else {
+                from
+            };
+            if let Some(distance) = generator.distance {
+                let angle = t.relative_direction.to_radians();
+                t.relative_position = (angle.sin()*distance, -angle.cos()*distance);
+            }
+            turrets.push(t);
         }
+    }
+
+    Ok((
+        Tank {
+            physics: Physics {
+                x: 0.,
+                y: 0.,
+                xvel: 0.,
+                yvel: 0.,
+                weight: def.weight,
+                rot: 0.,
+                rotvel: 0.,
+                collision_size: def.collision_size,
+                hp: def.hp,
+                max_hp: def.hp,
+                hp_regen: def.hp_regen,
+            },
+            turrets,
+            power: def.power,
+            rot_power: def.rot_power,
+            texture: def.texture.unwrap_or_else(|| name.to_owned()),
+            evolution: Evolution {
+                class: name.to_owned(),
+                ..Evolution::new()
+            },
+            ai: def.ai,
+            body_damage: def.body_damage,
+            knockback_resistance: def.knockback_resistance,
+            max_drones: def.max_drones,
+            infects: def.infects,
+            stealth: def.stealth,
+            ..Default::default()
+        },
+        def.evolve_to,
+        def.cost,
+    ))
+}
 
+/// Class every tank starts as
+pub const ROOT_CLASS: &str = "basic";
+/// Promoting uses the number keys 1-9, so a class can't evolve to more
+pub const MAX_EVOLUTIONS: usize = 9;
+/// Turrets firing more often than the fps glitch, see `Turret::reload_time`
+const MIN_RELOAD_TIME: f64 = 1. / 30.;
 
-        {
-            hash_set.insert("sniper".to_string(), (
-                Tank {
-                    physics: Physics {
-                        x: 0.,
-                        y: 0.,
-                        xvel: 0.,
-                        yvel: 0.,
-                        weight: 20.,
-                        rot: 0.,
-                        rotvel: 0.,
-                        collision_size: 35.,
-                        hp: 20.,
-                        max_hp: 20.,
-                        hp_regen: 1.2,
-                    },
-                    turrets: vec![Turret {
-                        projectile_impulse: 32_000.,
-                        projectile_weight: 25.,
-                        projectile_collision_size: 15.,
-                        projectile_hp_regen: -10.,
-                        projectile_hp: 25.,
-                        reload_time: 6.,
-                        relative_position: (0.,-60.),
-                        ..Default::default()
-                    }],
-                    power: 4000.,
-                    rot_power: 30.,
-                    texture: "sniper".to_owned(),
-                    ..Default::default()                
-                }, vec![
-                    "hailstorm".to_string(),
-                    "wide".to_string(),
-                    "shotgun".to_string(),
-                ],
-                1000.
-            ));
-            
-    
-            hash_set.insert("wide".to_string(), (
-                Tank {
-                    physics: Physics {
-                        x: 0.,
-                        y: 0.,
-                        xvel: 0.,
-                        yvel: 0.,
-                        weight: 80.,
-                        rot: 0.,
-                        rotvel: 0.,
-                        collision_size: 65.,
-                        hp: 80.,
-                        max_hp: 80.,
-                        hp_regen: 2.,
-                    },
-                    turrets: vec![Turret {
-                        projectile_impulse: 60_000.,
-                        projectile_weight: 100.,
-                        projectile_collision_size: 40.,
-                        projectile_hp_regen: -25.,
-                        projectile_hp: 100.,
-                        reload_time: 4.0,
-                        inaccuracy: 0.,
-                        relative_position: (0.,-90.),
-                        ..Default::default()
-                    }],
-                    power: 3000.,
-                    rot_power: 50.,
-                    texture: "wide".to_owned(),
-                    ..Default::default()                
-                }, vec![],
-                1000.
-            ));
-    
-    
-            hash_set.insert("shotgun".to_string(), (
-                Tank {
-                    physics: Physics {
-                        x: 0.,
-                        y: 0.,
-                        xvel: 0.,
-                        yvel: 0.,
-                        weight: 40.,
-                        rot: 0.,
-                        rotvel: 0.,
-                        collision_size: 50.,
-                        hp: 40.,
-                        max_hp: 40.,
-                        hp_regen: 1.4,
-                    },
-                    turrets: vec![],
-                    power: 3500.,
-                    rot_power: 60.,
-                    texture: "shotgun".to_owned(),
-                    ..Default::default()                
-                }, vec![
-    
-                ],
-                1000.
-            ));
-    
-            for a in 0..41 {
-                let angle = (a as f64 - 20.) / 10.;
-                hash_set.get_mut("shotgun").unwrap().0.turrets.push(Turret {
-                    projectile_impulse: 8_000.,
-                    projectile_weight: 8.,
-                    projectile_collision_size: 5.,
-                    projectile_hp_regen: -2.0,
-                    projectile_hp: 2.,
-                    reload_time: 6.0,
-                    inaccuracy: 6.,
-                    relative_position: (angle.to_radians().sin()*50.,-angle.to_radians().cos()*50.),
-                    relative_direction: angle,
-                    ..Default::default()
-                })
+/// Checks an evolution tree for mistakes that would otherwise only show up as a panic when someone evolves.
+/// `textures` are the names of all the textures the game loads, `required` the classes the code spawns by name.
+///
+/// Returns a readable description of every problem, empty if there are none
+pub fn validate(tree: &ClassTree, textures: &HashSet<String>, required: &[&str]) -> Vec<String> {
+    let mut problems = vec![];
+    let mut names: Vec<&String> = tree.keys().collect();
+    names.sort();
+
+    if !tree.contains_key(ROOT_CLASS) {
+        problems.push(format!("there is no \"{}\" class, which every tank starts as", ROOT_CLASS));
+    }
+    for class in required {
+        if !tree.contains_key(*class) {
+            problems.push(format!("there is no \"{}\" class, which a gamemode spawns", class));
+        }
+    }
+
+    for name in &names {
+        let (tank, evolve_to, cost) = &tree[*name];
+        let mut problem = |p: String| problems.push(format!("class \"{}\": {}", name, p));
+
+        for (i, target) in evolve_to.iter().enumerate() {
+            if !tree.contains_key(target) {
+                problem(format!("evolves to \"{}\", which does not exist", target));
+            }
+            if evolve_to[..i].contains(target) {
+                problem(format!("evolves to \"{}\" more than once", target));
+            }
+        }
+        if evolve_to.len() > MAX_EVOLUTIONS {
+            problem(format!("evolves to {} classes, at most {} fit on the number keys", evolve_to.len(), MAX_EVOLUTIONS));
+        }
+
+        if !textures.contains(&tank.texture) {
+            problem(format!("texture \"{}\" does not exist", tank.texture));
+        }
+        // without turrets the only way to fight is ramming
+        if tank.turrets.is_empty() && !tank.ai.rams {
+            problem("has no turrets, so bots have to ram with it (\"ai\": {\"rams\": true})".to_owned());
+        }
+        if tank.max_drones == Some(0) && tank.fires_drones() {
+            problem("max_drones is 0, so its drone turrets never fire".to_owned());
+        }
+
+        check("", &[
+            ("cost", *cost, *cost >= 0.),
+            ("weight", tank.physics.weight, tank.physics.weight > 0.),
+            ("collision_size", tank.physics.collision_size, tank.physics.collision_size > 0.),
+            ("hp", tank.physics.max_hp, tank.physics.max_hp > 0.),
+            ("hp_regen", tank.physics.hp_regen, tank.physics.hp_regen >= 0.),
+            ("power", tank.power, tank.power > 0.),
+            ("rot_power", tank.rot_power, tank.rot_power > 0.),
+            ("body_damage", tank.body_damage, tank.body_damage >= 0.),
+            ("knockback_resistance", tank.knockback_resistance, (0. ..=1.).contains(&tank.knockback_resistance)),
+            ("stealth", tank.stealth, tank.stealth >= 0.),
+        ]).into_iter().for_each(&mut problem);
+
+        for (i, turret) in tank.turrets.iter().enumerate() {
+            let mut problem = |p: String| problems.push(format!("class \"{}\": turret {}: {}", name, i, p));
+            if !textures.contains(&turret.projectile_texture) {
+                problem(format!("projectile texture \"{}\" does not exist", turret.projectile_texture));
+            }
+            check("", &[
+                ("projectile_impulse", turret.projectile_impulse, turret.projectile_impulse >= 0.),
+                ("projectile_weight", turret.projectile_weight, turret.projectile_weight > 0.),
+                ("projectile_collision_size", turret.projectile_collision_size, turret.projectile_collision_size > 0.),
+                ("projectile_hp", turret.projectile_hp, turret.projectile_hp > 0.),
+                // projectiles have to die at some point, bots also use this for the range
+                ("projectile_hp_regen", turret.projectile_hp_regen, turret.projectile_hp_regen < 0.),
+                ("reload_time", turret.reload_time, turret.reload_time >= MIN_RELOAD_TIME),
+                ("inaccuracy", turret.inaccuracy, turret.inaccuracy >= 0.),
+                ("relative_direction", turret.relative_direction, true),
+                ("delay", turret.delay, (0. ..=1.).contains(&turret.delay)),
+                ("recoil", turret.recoil, turret.recoil >= 0.),
+                ("auto_range", turret.auto_range, !turret.auto || turret.auto_range > 0.),
+                ("auto_arc", turret.auto_arc, (0. ..=180.).contains(&turret.auto_arc)),
+                ("relative_position x", turret.relative_position.0, true),
+                ("relative_position y", turret.relative_position.1, true),
+            ]).into_iter().for_each(&mut problem);
+            if let Some(bomb) = turret.projectile_kind.bomb() {
+                check("bomb ", &[
+                    ("fragment_speed", bomb.fragment_speed, bomb.fragment_speed >= 0.),
+                    ("fragment_lifetime", bomb.fragment_lifetime, bomb.fragment_lifetime > 0.),
+                    ("blast", bomb.blast, bomb.blast >= 0.),
+                ]).into_iter().for_each(&mut problem);
+            }
+            if let Some(laser) = &turret.laser {
+                if turret.auto {
+                    problem("auto turrets can not be lasers".to_owned());
+                }
+                if laser.penetration == 0 {
+                    problem("laser penetration is 0, so it never hits anything".to_owned());
+                }
+                check("laser ", &[
+                    ("damage", laser.damage, laser.damage >= 0.),
+                    ("range", laser.range, laser.range > 0.),
+                    ("charge", laser.charge, laser.charge >= 0.),
+                    ("width", laser.width, laser.width >= 0.),
+                    ("beam_time", laser.beam_time, laser.beam_time >= 0.),
+                ]).into_iter().for_each(&mut problem);
+            }
+            if let Some(rocket) = &turret.rocket {
+                check("rocket ", &[
+                    ("thrust", rocket.thrust, rocket.thrust >= 0.),
+                    ("fuel", rocket.fuel, rocket.fuel >= 0.),
+                    ("turn_rate", rocket.turn_rate, rocket.turn_rate >= 0.),
+                    ("homing_range", rocket.homing_range, rocket.homing_range > 0.),
+                ]).into_iter().for_each(&mut problem);
+            }
+        }
+    }
+
+    // cycles, an evolution can't lead back to a class the tank already was
+    let mut finished = HashSet::new();
+    for name in &names {
+        find_cycles(name, tree, &mut vec![], &mut finished, &mut problems);
+    }
+
+    // unreachable classes
+    let mut reachable = HashSet::new();
+    let mut queue = vec![ROOT_CLASS];
+    while let Some(class) = queue.pop() {
+        if reachable.insert(class) {
+            if let Some((_, evolve_to, _)) = tree.get(class) {
+                queue.extend(evolve_to.iter().map(|c| c.as_str()));
+            }
+        }
+    }
+    if tree.contains_key(ROOT_CLASS) {
+        for name in &names {
+            if !reachable.contains(name.as_str()) {
+                problems.push(format!("class \"{}\": can't be reached by evolving from \"{}\"", name, ROOT_CLASS));
             }
         }
+    }
 
+    problems
+}
 
-        // Add more entries here using hash_set.insert() as needed
+/// Problems of the stats that are not finite or not `valid`, as "`prefix``name` is `value`"
+fn check(prefix: &str, stats: &[(&str, f64, bool)]) -> Vec<String> {
+    stats.iter()
+        .filter(|(_, value, valid)| !value.is_finite() || !valid)
+        .map(|(stat, value, _)| format!("{}{} is {}", prefix, stat, value))
+        .collect()
+}
 
-        hash_set
+/// Depth first search for evolutions leading back to a class in `path`
+fn find_cycles<'a>(name: &'a str, tree: &'a ClassTree, path: &mut Vec<&'a str>, finished: &mut HashSet<&'a str>, problems: &mut Vec<String>) {
+    if finished.contains(name) {
+        return
+    }
+    if let Some(start) = path.iter().position(|c| *c == name) {
+        problems.push(format!("evolutions go in a circle: {} -> {}", path[start..].join(" -> "), name));
+        return
+    }
+    let Some((_, evolve_to, _)) = tree.get(name) else {
+        return
     };
-}
\ No newline at end of file
+    path.push(name);
+    for target in evolve_to {
+        find_cycles(target, tree, path, finished, problems);
+    }
+    path.pop();
+    finished.insert(name);
+}
@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{normalize, Physics};
+
+/// A static obstacle, a convex polygon. Blocks tanks, shapes and bullets, see `Map::collide_walls`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Wall {
+    /// corners in map coordinates, in order around the polygon. The polygon must be convex
+    pub points: Vec<(f64, f64)>,
+}
+impl Wall {
+    /// Axis aligned box, `x` and `y` is the top left corner
+    pub fn rect(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Wall {
+            points: vec![(x, y), (x + width, y), (x + width, y + height), (x, y + height)],
+        }
+    }
+
+    /// Panics if the polygon has less than 3 corners. Does not check if it is convex
+    pub fn polygon(points: Vec<(f64, f64)>) -> Self {
+        assert!(points.len() >= 3, "a wall needs at least 3 corners");
+        Wall { points }
+    }
+
+    /// Top left and bottom right corner of the box around the wall
+    pub fn bounds(&self) -> ((f64, f64), (f64, f64)) {
+        let mut min = self.points[0];
+        let mut max = self.points[0];
+        for p in &self.points {
+            min = (min.0.min(p.0), min.1.min(p.1));
+            max = (max.0.max(p.0), max.1.max(p.1));
+        }
+        (min, max)
+    }
+
+    fn edges(&self) -> impl Iterator<Item = ((f64, f64), (f64, f64))> + '_ {
+        self.points.iter().zip(self.points.iter().cycle().skip(1)).map(|(a, b)| (*a, *b))
+    }
+
+    pub fn contains(&self, (x, y): (f64, f64)) -> bool {
+        // inside a convex polygon the point is on the same side of every edge
+        let mut side = 0.;
+        for (a, b) in self.edges() {
+            let cross = (b.0 - a.0) * (y - a.1) - (b.1 - a.1) * (x - a.0);
+            if cross * side < 0. {
+                return false
+            }
+            if cross != 0. {
+                side = cross;
+            }
+        }
+        true
+    }
+
+    /// The point on the border of the wall closest to `p`
+    pub fn closest_point(&self, p: (f64, f64)) -> (f64, f64) {
+        let mut closest = self.points[0];
+        let mut closest_dist = f64::MAX;
+        for (a, b) in self.edges() {
+            let edge = (b.0 - a.0, b.1 - a.1);
+            let t = (((p.0 - a.0) * edge.0 + (p.1 - a.1) * edge.1) / (edge.0.powi(2) + edge.1.powi(2))).clamp(0., 1.);
+            let on_edge = (a.0 + edge.0 * t, a.1 + edge.1 * t);
+            let dist = (p.0 - on_edge.0).powi(2) + (p.1 - on_edge.1).powi(2);
+            if dist < closest_dist {
+                closest_dist = dist;
+                closest = on_edge;
+            }
+        }
+        closest
+    }
+
+    /// How far from `from` a ray in the normalized direction `dir` enters the wall, None if it misses. 0 if `from` is inside
+    pub fn raycast(&self, from: (f64, f64), dir: (f64, f64)) -> Option<f64> {
+        if self.contains(from) {
+            return Some(0.)
+        }
+        let mut hit: Option<f64> = None;
+        for (a, b) in self.edges() {
+            let edge = (b.0 - a.0, b.1 - a.1);
+            let denom = dir.0 * edge.1 - dir.1 * edge.0;
+            if denom == 0. {
+                continue
+            }
+            let diff = (a.0 - from.0, a.1 - from.1);
+            // distance along the ray, and where on the edge
+            let t = (diff.0 * edge.1 - diff.1 * edge.0) / denom;
+            let u = (diff.0 * dir.1 - diff.1 * dir.0) / denom;
+            if t >= 0. && (0. ..=1.).contains(&u) {
+                hit = Some(hit.map_or(t, |hit: f64| hit.min(t)));
+            }
+        }
+        hit
+    }
+
+    /// Pushes the object out of the wall and stops its movement into the wall. Returns true if the object touched the wall
+    pub fn block(&self, physics: &mut Physics) -> bool {
+        let p = (physics.x, physics.y);
+        let (min, max) = self.bounds();
+        if p.0 + physics.collision_size < min.0 || p.0 - physics.collision_size > max.0 || p.1 + physics.collision_size < min.1 || p.1 - physics.collision_size > max.1 {
+            return false
+        }
+
+        let inside = self.contains(p);
+        let closest = self.closest_point(p);
+        let dist = ((p.0 - closest.0).powi(2) + (p.1 - closest.1).powi(2)).sqrt();
+        if !inside && dist >= physics.collision_size {
+            return false
+        }
+
+        // points out of the wall
+        let normal = if inside {
+            normalize((closest.0 - p.0, closest.1 - p.1))
+        } else {
+            normalize((p.0 - closest.0, p.1 - closest.1))
+        };
+        physics.x = closest.0 + normal.0 * physics.collision_size;
+        physics.y = closest.1 + normal.1 * physics.collision_size;
+
+        let into_wall = physics.xvel * normal.0 + physics.yvel * normal.1;
+        if into_wall < 0. {
+            physics.xvel -= normal.0 * into_wall;
+            physics.yvel -= normal.1 * into_wall;
+        }
+        true
+    }
+}
@@ -0,0 +1,119 @@
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// One step of a zone schedule. The zone waits, then moves and shrinks to `center` and `radius`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ZonePhase {
+    /// seconds the zone stays still before it starts shrinking
+    pub wait: f64,
+    /// seconds it takes to get to the new center and radius
+    pub shrink: f64,
+    /// in map coordinates
+    pub center: (f64, f64),
+    pub radius: f64,
+    /// hp per second taken by tanks outside the zone during this phase
+    pub damage: f64,
+}
+
+/// A schedule of 5 phases for a map of this size. Every zone is inside the previous one, each phase deals twice the damage of the previous
+pub fn default_schedule(map_size: (f64, f64)) -> Vec<ZonePhase> {
+    let mut schedule = vec![];
+    let mut center = (0., 0.);
+    let mut radius = (map_size.0.powi(2) + map_size.1.powi(2)).sqrt();
+    let mut damage = 1.;
+    for _ in 0..5 {
+        let new_radius = radius * 0.55;
+        // the new zone must fit into the old one, and its center must be on the map
+        let angle = thread_rng().gen::<f64>() * std::f64::consts::TAU;
+        let offset = thread_rng().gen::<f64>() * (radius - new_radius);
+        center = (
+            (center.0 + angle.cos()*offset).clamp(-map_size.0, map_size.0),
+            (center.1 + angle.sin()*offset).clamp(-map_size.1, map_size.1),
+        );
+        radius = new_radius;
+        schedule.push(ZonePhase {wait: 30., shrink: 30., center, radius, damage});
+        damage *= 2.;
+    }
+    schedule
+}
+
+/// Reads a schedule from a JSON file with a list of `ZonePhase`s
+pub fn load_schedule(path: &str) -> Result<Vec<ZonePhase>, String> {
+    let data = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let schedule: Vec<ZonePhase> = serde_json::from_str(&data).map_err(|e| e.to_string())?;
+    if schedule.is_empty() {
+        return Err("the zone schedule has no phases".to_owned())
+    }
+    Ok(schedule)
+}
+
+/// Circular safe zone of a battle royale. Follows its schedule, tanks outside of it take damage, see `Map::update_zone`
+pub struct Zone {
+    schedule: Vec<ZonePhase>,
+    /// index into the schedule, equal to its length once the last phase is over
+    phase: usize,
+    /// seconds since the current phase started
+    time: f64,
+    /// where the current phase started from
+    from: ((f64, f64), f64),
+    /// the zone before the first phase, covers the whole map
+    start: ((f64, f64), f64),
+    pub center: (f64, f64),
+    pub radius: f64,
+}
+impl Zone {
+    pub fn new(schedule: Vec<ZonePhase>, map_size: (f64, f64)) -> Self {
+        let start = ((0., 0.), (map_size.0.powi(2) + map_size.1.powi(2)).sqrt());
+        Zone {
+            schedule,
+            phase: 0,
+            time: 0.,
+            from: start,
+            start,
+            center: start.0,
+            radius: start.1,
+        }
+    }
+
+    /// Back to the start of the schedule, for a new round
+    pub fn restart(&mut self) {
+        self.phase = 0;
+        self.time = 0.;
+        self.from = self.start;
+        self.center = self.start.0;
+        self.radius = self.start.1;
+    }
+
+    pub fn update(&mut self, delta: f64) {
+        let Some(p) = self.schedule.get(self.phase) else {
+            return
+        };
+        self.time += delta;
+        let progress = if p.shrink > 0. {((self.time - p.wait) / p.shrink).clamp(0., 1.)} else if self.time >= p.wait {1.} else {0.};
+        self.center = (
+            self.from.0.0 + (p.center.0 - self.from.0.0) * progress,
+            self.from.0.1 + (p.center.1 - self.from.0.1) * progress,
+        );
+        self.radius = self.from.1 + (p.radius - self.from.1) * progress;
+
+        if self.time >= p.wait + p.shrink {
+            self.from = (p.center, p.radius);
+            self.phase += 1;
+            self.time = 0.;
+        }
+    }
+
+    /// Hp per second taken outside the zone right now. The last phase keeps dealing its damage after it ended
+    pub fn damage(&self) -> f64 {
+        self.schedule[self.phase.min(self.schedule.len() - 1)].damage
+    }
+
+    /// Center and radius the zone is moving to, None after the last phase
+    pub fn next(&self) -> Option<((f64, f64), f64)> {
+        self.schedule.get(self.phase).map(|p| (p.center, p.radius))
+    }
+
+    pub fn contains(&self, (x, y): (f64, f64)) -> bool {
+        (x - self.center.0).powi(2) + (y - self.center.1).powi(2) < self.radius.powi(2)
+    }
+}